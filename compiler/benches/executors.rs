@@ -0,0 +1,69 @@
+//! Steps per second of `MachineExecutor`, `DenseExecutor` and
+//! `AcceleratedExecutor` on the same machines, run with `cargo bench --bench executors`.
+
+use std::time::Instant;
+
+use compiler::compact;
+use compiler::sim::{
+    AcceleratedExecutor, DenseExecutor, Limits, MachineExecutor, RunOutcome, Simulator,
+};
+use compiler::tm::Machine;
+
+/// Steps of the runs both executors do.
+const STEPS: usize = 1_000_000;
+
+fn measure(name: &str, steps: usize, run: impl FnOnce() -> RunOutcome) -> f64 {
+    let start = Instant::now();
+    let outcome = run();
+    let seconds = start.elapsed().as_secs_f64();
+    let rate = steps as f64 / seconds;
+    println!(
+        "{:<40} {:>12} steps {:>10.3} s {:>14.0} steps/s ({:?})",
+        name, steps, seconds, rate, outcome
+    );
+    rate
+}
+
+fn compare(name: &str, machine: &Machine) {
+    let limits = Limits {
+        steps: Some(STEPS),
+        ..Limits::default()
+    };
+    let mut executor = MachineExecutor::new(machine.clone(), vec![]);
+    let mut steps = 0;
+    let plain = measure(&format!("{} MachineExecutor", name), STEPS, || {
+        executor.run_with(&limits, |_| steps += 1)
+    });
+    let mut dense = DenseExecutor::new(machine, vec![]).unwrap();
+    let fast = measure(&format!("{} DenseExecutor", name), STEPS, || {
+        dense.execute(&limits)
+    });
+    assert_eq!(dense.steps(), steps);
+    assert_eq!(dense.band_symbols(), executor.band_symbols());
+    println!("{:<40} {:>.1}x\n", "", fast / plain);
+}
+
+fn main() {
+    // the 5 state busy beaver champion, halts after 47,176,870 steps
+    let busy_beaver = compact::from_compact("1RB1LC_1RC1RB_1RD0LE_1LA1LD_1RZ0LA").unwrap();
+    // never halts, the tape grows in both directions
+    let bouncer = compact::from_compact("1RB0LC_0LA1RC_1LA0RB").unwrap();
+
+    compare("BB(5)", &busy_beaver);
+    compare("bouncer", &bouncer);
+
+    let mut dense = DenseExecutor::new(&busy_beaver, vec![]).unwrap();
+    let outcome = dense.execute(&Limits::default());
+    assert_eq!(outcome, RunOutcome::Accepted);
+    let steps = dense.steps();
+    let mut dense = DenseExecutor::new(&busy_beaver, vec![]).unwrap();
+    measure("BB(5) to the end, DenseExecutor", steps, || {
+        dense.execute(&Limits::default())
+    });
+
+    let mut accelerated = AcceleratedExecutor::new(&busy_beaver, vec![]).unwrap();
+    measure("BB(5) to the end, AcceleratedExecutor", steps, || {
+        accelerated.execute(&Limits::default())
+    });
+    assert_eq!(accelerated.band_symbols(), dense.band_symbols());
+}
@@ -1,14 +1,15 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-use crate::tm::{Direction, Machine, State};
+use crate::tm::{Direction, Machine, State, TransitionFunction, WILDCARD};
 
 use either::Either;
 
+#[derive(Clone)]
 pub struct TmDef {
     identifier: String,
     alphabet: HashSet<String>,
-    block: TmBlock,
+    pub(crate) block: TmBlock,
 }
 
 impl TmDef {
@@ -19,9 +20,14 @@ impl TmDef {
             block,
         }
     }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
 }
 
-pub struct TmBlock(Vec<TmStmt>);
+#[derive(Clone)]
+pub struct TmBlock(pub(crate) Vec<TmStmt>);
 
 impl TmBlock {
     pub fn new(stats: Vec<TmStmt>) -> TmBlock {
@@ -29,6 +35,7 @@ impl TmBlock {
     }
 }
 
+#[derive(Clone)]
 pub enum TmStmt {
     Step(TmStep),
     Branch { condition: TmStep, body: TmBlock },
@@ -52,23 +59,54 @@ impl AtomicTmStep {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TmStep {
-    cases: HashMap<String, TmOperation>,
-    default: Option<TmOperation>,
+    pub(crate) cases: HashMap<String, TmOperation>,
+    pub(crate) default: Option<TmOperation>,
+    /// Sorted patterns of every `or` alternative, a later alternative takes
+    /// a pattern from an earlier one like in `cases`.
+    alternatives: Vec<Vec<String>>,
+    /// Source line of the statement, for coverage.
+    line: Option<usize>,
 }
 
 impl TmStep {
     pub fn new(atomic_steps: Vec<AtomicTmStep>, default: Option<TmOperation>) -> TmStep {
         let mut cases = HashMap::new();
+        let mut alternatives = Vec::new();
 
         for atomic_step in atomic_steps {
-            for pattern in atomic_step.patterns {
-                cases.insert(pattern, atomic_step.operation.clone());
+            let mut patterns = atomic_step.patterns.into_iter().collect::<Vec<_>>();
+            patterns.sort();
+            for pattern in patterns.iter() {
+                cases.insert(pattern.clone(), atomic_step.operation.clone());
             }
+            alternatives.push(patterns);
         }
 
-        TmStep { cases, default }
+        TmStep {
+            cases,
+            default,
+            alternatives,
+            line: None,
+        }
+    }
+
+    pub fn at_line(mut self, line: usize) -> TmStep {
+        self.line = Some(line);
+        self
+    }
+
+    /// Does nothing and goes on with the next statement, stands in for
+    /// empty blocks.
+    fn identity() -> TmStep {
+        TmStep::new(
+            vec![],
+            Some(TmOperation::Move {
+                replace: None,
+                direction: TmDir::Stay,
+            }),
+        )
     }
 }
 
@@ -108,14 +146,23 @@ pub fn into_steps(def: TmDef) -> HashMap<u32, (TmStep, u32, u32)> {
                     })
                     .collect::<Vec<_>>();
 
-                let len = instructions.len();
-                instructions[len - 1].2 = next;
+                match instructions.last_mut() {
+                    Some(last) => last.2 = next,
+                    None => {
+                        steps.insert(curr, (TmStep::identity(), next, outer));
+                    }
+                }
                 queue.append(&mut instructions);
             }
             Either::Right(TmStmt::Cycle(block)) => {
                 queue.push((Either::Left(block), curr, curr, next));
             }
-            Either::Right(TmStmt::Branch { condition, body }) => {
+            Either::Right(TmStmt::Branch {
+                mut condition,
+                body,
+            }) => {
+                // a symbol without a case skips the body
+                condition.default.get_or_insert(TmOperation::Break);
                 let body_st = state_counter.next().unwrap();
                 queue.push((Either::Right(TmStmt::Step(condition)), curr, body_st, next));
                 queue.push((Either::Left(body), body_st, outer, outer));
@@ -128,3 +175,151 @@ pub fn into_steps(def: TmDef) -> HashMap<u32, (TmStep, u32, u32)> {
 
     steps
 }
+
+/// Where the states of a compiled program come from, states of statements
+/// without a line are left out.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    pub statements: HashMap<String, StatementSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementSource {
+    pub line: usize,
+    /// The `or` alternatives, followed by the default if the step has one.
+    pub alternatives: Vec<String>,
+    /// Index into `alternatives` of every transition function of the state.
+    pub functions: Vec<usize>,
+}
+
+fn state_name(state: u32) -> String {
+    format!("q{}", state)
+}
+
+/// Compiles the program into a one band machine that starts in `q1`. Halting
+/// and leaving the program end in `q0`, a symbol without a case and without a
+/// default rejects.
+pub fn into_machine(def: TmDef) -> Result<(Machine, SourceMap), &'static str> {
+    let alphabet = def.alphabet.clone();
+    let mut states = HashMap::new();
+    let mut source_map = SourceMap::default();
+    states.insert(state_name(0), State::new(vec![], true));
+
+    for (curr, (step, next, outer)) in into_steps(def) {
+        let function = |read: &str, operation: &TmOperation| {
+            let (written, direction, next_state) = match operation {
+                TmOperation::Move { replace, direction } => {
+                    let direction = match direction {
+                        TmDir::Left => Direction::Left,
+                        TmDir::Right => Direction::Right,
+                        TmDir::Stay => Direction::Unchanged,
+                    };
+                    (replace.as_deref().unwrap_or(WILDCARD), direction, next)
+                }
+                TmOperation::Break => (WILDCARD, Direction::Unchanged, outer),
+                TmOperation::Halt => (WILDCARD, Direction::Unchanged, 0),
+            };
+            TransitionFunction::new(
+                state_name(curr),
+                vec![read.to_string()],
+                vec![(written.to_string(), direction)],
+                state_name(next_state),
+            )
+        };
+
+        let mut owner = HashMap::new();
+        for (index, patterns) in step.alternatives.iter().enumerate() {
+            for pattern in patterns {
+                owner.insert(pattern, index);
+            }
+        }
+        let mut functions = Vec::new();
+        let mut source = StatementSource {
+            line: step.line.unwrap_or_default(),
+            alternatives: Vec::new(),
+            functions: Vec::new(),
+        };
+        for (index, patterns) in step.alternatives.iter().enumerate() {
+            for pattern in patterns.iter().filter(|pattern| owner[pattern] == index) {
+                functions.push(function(pattern, &step.cases[pattern]));
+                source.functions.push(index);
+            }
+            source.alternatives.push(patterns.join(", "));
+        }
+        if let Some(default) = &step.default {
+            functions.push(function(WILDCARD, default));
+            source.functions.push(source.alternatives.len());
+            source.alternatives.push(match default {
+                TmOperation::Break => "break".to_string(),
+                TmOperation::Halt => "halt".to_string(),
+                TmOperation::Move { .. } => "default".to_string(),
+            });
+        }
+
+        if step.line.is_some() {
+            source_map.statements.insert(state_name(curr), source);
+        }
+        states.insert(state_name(curr), State::new(functions, false));
+    }
+
+    let machine = Machine::new(alphabet, 1, states, state_name(1))?;
+    Ok((machine, source_map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(pattern: &str, default: Option<TmOperation>) -> TmStep {
+        let atomic = AtomicTmStep::new(vec![pattern.to_string()], None, TmDir::Right);
+        TmStep::new(vec![atomic], default)
+    }
+
+    fn def(statements: Vec<TmStmt>) -> TmDef {
+        let alphabet = ["a"].iter().map(|s| s.to_string()).collect();
+        TmDef::new("test".to_string(), alphabet, TmBlock::new(statements))
+    }
+
+    #[test]
+    fn empty_blocks() {
+        let steps = into_steps(def(vec![]));
+        let (step, next, _) = &steps[&1];
+        assert_eq!(*next, 0);
+        assert!(step.cases.is_empty());
+        assert_eq!(
+            step.default,
+            Some(TmOperation::Move {
+                replace: None,
+                direction: TmDir::Stay,
+            })
+        );
+
+        // an empty cycle loops on its identity step
+        let steps = into_steps(def(vec![TmStmt::Cycle(TmBlock::new(vec![]))]));
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[&1].1, 1);
+    }
+
+    #[test]
+    fn branch_without_default() {
+        let steps = into_steps(def(vec![
+            TmStmt::Branch {
+                condition: step("a", None),
+                body: TmBlock::new(vec![TmStmt::Step(step("a", None))]),
+            },
+            TmStmt::Step(step("a", None)),
+        ]));
+        // a symbol without a case breaks to the statement after the branch
+        let (condition, _, outer) = &steps[&1];
+        assert_eq!(condition.default, Some(TmOperation::Break));
+        assert_eq!(*outer, 2);
+        assert!(steps.contains_key(&2));
+
+        // an explicit default is kept
+        let steps = into_steps(def(vec![TmStmt::Branch {
+            condition: step("a", Some(TmOperation::Halt)),
+            body: TmBlock::new(vec![]),
+        }]));
+        assert_eq!(steps[&1].0.default, Some(TmOperation::Halt));
+    }
+}
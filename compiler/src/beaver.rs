@@ -0,0 +1,590 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, BufRead};
+
+use serde::{Deserialize, Serialize};
+
+use crate::compact::{state_letter, symbol_name};
+use crate::sim::{DenseExecutor, Limits, MachineExecutor, RunOutcome};
+use crate::tm::{Direction, Machine, State, TapeMode, TransitionFunction};
+
+/// Most configurations `BackwardReasoning` looks at before giving up.
+const MAX_BACKWARD_CONFIGURATIONS: usize = 100_000;
+
+/// A machine of the enumeration, transitions that weren't reached yet are
+/// undefined and halt. Written in compact notation with `---` for those.
+#[derive(Debug, Clone)]
+struct Code {
+    states: usize,
+    symbols: usize,
+    /// The symbol to write, the direction and the next state of every state
+    /// and symbol.
+    transitions: Vec<Option<(usize, Direction, usize)>>,
+}
+
+impl Code {
+    fn root(states: usize, symbols: usize) -> Self {
+        Self {
+            states,
+            symbols,
+            transitions: vec![None; states * symbols],
+        }
+    }
+
+    /// A one band machine without end states, an undefined transition
+    /// rejects instead of halting.
+    fn to_machine(&self) -> Machine {
+        let name = |state: usize| state_letter(state).unwrap().to_string();
+        let states = (0..self.states)
+            .map(|state| {
+                let functions = (0..self.symbols)
+                    .filter_map(|symbol| {
+                        let (write, direction, next) =
+                            self.transitions[state * self.symbols + symbol].clone()?;
+                        Some(TransitionFunction::new(
+                            name(state),
+                            vec![symbol_name(symbol as u32)],
+                            vec![(symbol_name(write as u32), direction)],
+                            name(next),
+                        ))
+                    })
+                    .collect();
+                (name(state), State::new(functions, false))
+            })
+            .collect();
+        let alphabet = (0..self.symbols as u32).map(symbol_name).collect();
+        Machine::new(alphabet, 1, states, name(0))
+            .unwrap()
+            .with_tape_mode(TapeMode::TwoWayInfinite)
+    }
+
+    /// The machines that define the transition of `state` and `symbol`, in
+    /// tree normal form: a transition only goes to the first state that isn't
+    /// used yet and only writes the first symbol that isn't, and the first
+    /// transition moves right. Empty if it is the last undefined transition,
+    /// since then no child can halt.
+    fn children(&self, state: usize, symbol: usize) -> Vec<Code> {
+        let defined = self.transitions.iter().flatten().collect::<Vec<_>>();
+        if defined.len() + 1 == self.transitions.len() {
+            return Vec::new();
+        }
+        let states = defined.iter().map(|t| t.2).max().unwrap_or(0) + 2;
+        let symbols = defined.iter().map(|t| t.0).max().unwrap_or(0) + 2;
+        let directions = if defined.is_empty() {
+            vec![Direction::Right]
+        } else {
+            vec![Direction::Left, Direction::Right]
+        };
+
+        let mut children = Vec::new();
+        for write in 0..symbols.min(self.symbols) {
+            for direction in directions.iter() {
+                for next in 0..states.min(self.states) {
+                    let mut child = self.clone();
+                    child.transitions[state * self.symbols + symbol] =
+                        Some((write, direction.clone(), next));
+                    children.push(child);
+                }
+            }
+        }
+        children
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, transition) in self.transitions.iter().enumerate() {
+            if index > 0 && index % self.symbols == 0 {
+                f.write_str("_")?;
+            }
+            match transition {
+                Some((write, direction, next)) => {
+                    let direction = match direction {
+                        Direction::Left => 'L',
+                        _ => 'R',
+                    };
+                    write!(f, "{}{}{}", write, direction, state_letter(*next).unwrap())?
+                }
+                None => f.write_str("---")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the enumeration found out about a machine.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Halts on the undefined transition of `state` and `symbol`, the halting
+    /// transition counts as a step like in the busy beaver literature.
+    Halts {
+        steps: usize,
+        state: String,
+        symbol: String,
+    },
+    /// Proven by the decider with this name.
+    NeverHalts { decider: String },
+    /// Didn't halt within the step budget and no decider could prove it.
+    Undecided,
+}
+
+/// The first line of a results file, the verdicts only hold for the
+/// enumeration they were found by.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parameters {
+    pub states: usize,
+    pub symbols: usize,
+    pub steps: usize,
+}
+
+impl fmt::Display for Parameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} states, {} symbols and {} steps",
+            self.states, self.symbols, self.steps
+        )
+    }
+}
+
+/// One line of a results file after the first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Classified {
+    /// In compact notation.
+    pub machine: String,
+    pub verdict: Verdict,
+}
+
+/// Proves that machines don't halt when started on a blank band. Deciders
+/// only get machines that didn't halt within `steps` steps.
+pub trait Decider {
+    /// The name of the proof if there is one, it goes into `Verdict`.
+    fn never_halts(&self, machine: &Machine, steps: usize) -> Option<&'static str>;
+}
+
+/// The configuration repeats, as a "cycler", or repeats shifted along the
+/// band, as a "translated cycler", see `CycleDetector`.
+pub struct Cycler;
+
+impl Decider for Cycler {
+    fn never_halts(&self, machine: &Machine, steps: usize) -> Option<&'static str> {
+        let limits = Limits {
+            steps: Some(steps),
+            ..Limits::default()
+        };
+        let outcome =
+            MachineExecutor::new(machine.clone(), vec![]).run_detecting_cycles(&limits, |_| {});
+        match outcome {
+            RunOutcome::Cycle { .. } => Some("cycler"),
+            RunOutcome::TranslatedCycle { .. } => Some("translated cycler"),
+            _ => None,
+        }
+    }
+}
+
+/// The function that fires in `state` on `symbol`, if any, on a machine with
+/// one band and without wildcards.
+fn fires<'a>(state: &'a State, symbol: &str) -> Option<&'a TransitionFunction> {
+    state
+        .transition_functions
+        .iter()
+        .find(|f| f.bands_requirements[0] == symbol)
+}
+
+/// A configuration going back from a halt, only the cells the steps since
+/// read are known.
+#[derive(Clone)]
+struct Partial {
+    state: String,
+    head: isize,
+    cells: HashMap<isize, String>,
+}
+
+/// Goes back from every halting configuration. If no way back is `depth`
+/// steps long, a run that halts is shorter than that, and the machine
+/// doesn't halt within `depth` steps on a blank band, so it never does.
+pub struct BackwardReasoning {
+    pub depth: usize,
+}
+
+impl Decider for BackwardReasoning {
+    fn never_halts(&self, machine: &Machine, steps: usize) -> Option<&'static str> {
+        self.proves(machine, steps).then(|| "backward reasoning")
+    }
+}
+
+impl BackwardReasoning {
+    fn proves(&self, machine: &Machine, steps: usize) -> bool {
+        if machine.size != 1 || machine.tape_mode != TapeMode::TwoWayInfinite {
+            return false;
+        }
+        if steps < self.depth {
+            let halts = DenseExecutor::new(machine, vec![])
+                .map(|mut executor| {
+                    executor.execute(&Limits {
+                        steps: Some(self.depth),
+                        ..Limits::default()
+                    }) != RunOutcome::StepLimitExceeded
+                })
+                .unwrap_or(true);
+            if halts {
+                return false;
+            }
+        }
+        let machine = machine.expand_wildcards();
+        let mut stack = Vec::new();
+        for (name, state) in machine.states.iter() {
+            if state.is_end_state {
+                stack.push((name.clone(), 0, HashMap::new()));
+                continue;
+            }
+            for symbol in machine.alphabet.iter() {
+                if fires(state, symbol).is_none() {
+                    stack.push((name.clone(), 0, HashMap::from([(0, symbol.clone())])));
+                }
+            }
+        }
+        let mut stack = stack
+            .into_iter()
+            .map(|(state, head, cells)| (Partial { state, head, cells }, 0))
+            .collect::<Vec<_>>();
+
+        let mut configurations = 0;
+        while let Some((partial, depth)) = stack.pop() {
+            if depth == self.depth {
+                return false;
+            }
+            configurations += 1;
+            if configurations > MAX_BACKWARD_CONFIGURATIONS {
+                return false;
+            }
+            for (name, state) in machine.states.iter() {
+                for symbol in machine.alphabet.iter() {
+                    let f = match fires(state, symbol) {
+                        Some(f) if f.next_state_name == partial.state => f,
+                        _ => continue,
+                    };
+                    let written = f.written(0, symbol);
+                    let direction = &f.bands_actions[0].1;
+                    let previous = match direction {
+                        Direction::Right => partial.head - 1,
+                        Direction::Left => partial.head + 1,
+                        Direction::Unchanged => partial.head,
+                    };
+                    if partial
+                        .cells
+                        .get(&previous)
+                        .map_or(false, |cell| cell != written)
+                    {
+                        continue;
+                    }
+                    let mut cells = partial.cells.clone();
+                    cells.insert(previous, symbol.clone());
+                    let partial = Partial {
+                        state: name.clone(),
+                        head: previous,
+                        cells,
+                    };
+                    stack.push((partial, depth + 1));
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Counts of the verdicts of an enumeration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub machines: usize,
+    pub halting: usize,
+    /// By decider.
+    pub never_halting: BTreeMap<String, usize>,
+    pub undecided: Vec<String>,
+    /// The halting machine with the most steps, the busy beaver candidate.
+    pub champion: Option<(String, usize)>,
+}
+
+impl Summary {
+    fn add(&mut self, classified: &Classified) {
+        self.machines += 1;
+        match &classified.verdict {
+            Verdict::Halts { steps, .. } => {
+                self.halting += 1;
+                if self.champion.as_ref().map_or(true, |(_, max)| steps > max) {
+                    self.champion = Some((classified.machine.clone(), *steps));
+                }
+            }
+            Verdict::NeverHalts { decider } => {
+                *self.never_halting.entry(decider.clone()).or_default() += 1
+            }
+            Verdict::Undecided => self.undecided.push(classified.machine.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "machines: {}", self.machines)?;
+        writeln!(f, "halting: {}", self.halting)?;
+        for (decider, count) in self.never_halting.iter() {
+            writeln!(f, "never halting, {}: {}", decider, count)?;
+        }
+        writeln!(f, "undecided: {}", self.undecided.len())?;
+        if let Some((machine, steps)) = &self.champion {
+            writeln!(f, "champion: {} halts after {} steps", machine, steps)?;
+        }
+        Ok(())
+    }
+}
+
+/// Enumerates the machines with `states` states and `symbols` symbols in
+/// tree normal form. Every machine runs until it reaches an undefined
+/// transition, which halts and branches into the machines that define it, or
+/// for `steps` steps, after which the deciders try to prove that it never
+/// halts.
+pub struct Enumeration {
+    states: usize,
+    symbols: usize,
+    steps: usize,
+    deciders: Vec<Box<dyn Decider>>,
+}
+
+impl Enumeration {
+    /// With no deciders, see `with_decider` and `with_default_deciders`.
+    pub fn new(states: usize, symbols: usize, steps: usize) -> Result<Self, &'static str> {
+        if !(1..=25).contains(&states) {
+            return Err("expected between 1 and 25 states");
+        }
+        if !(2..=10).contains(&symbols) {
+            return Err("expected between 2 and 10 symbols");
+        }
+        Ok(Self {
+            states,
+            symbols,
+            steps,
+            deciders: Vec::new(),
+        })
+    }
+
+    pub fn parameters(&self) -> Parameters {
+        Parameters {
+            states: self.states,
+            symbols: self.symbols,
+            steps: self.steps,
+        }
+    }
+
+    /// Deciders are tried in the order they were added.
+    pub fn with_decider(mut self, decider: impl Decider + 'static) -> Self {
+        self.deciders.push(Box::new(decider));
+        self
+    }
+
+    pub fn with_default_deciders(self) -> Self {
+        let depth = self.steps.min(30);
+        self.with_decider(Cycler)
+            .with_decider(BackwardReasoning { depth })
+    }
+
+    fn classify(&self, code: &Code) -> Verdict {
+        let machine = code.to_machine();
+        let mut executor = DenseExecutor::new(&machine, vec![]).unwrap();
+        let limits = Limits {
+            steps: Some(self.steps),
+            ..Limits::default()
+        };
+        if let RunOutcome::Rejected { symbols } = executor.execute(&limits) {
+            return Verdict::Halts {
+                steps: executor.steps() + 1,
+                state: executor.current_state_name().to_string(),
+                symbol: symbols[0].clone(),
+            };
+        }
+        self.deciders
+            .iter()
+            .find_map(|decider| decider.never_halts(&machine, self.steps))
+            .map_or(Verdict::Undecided, |decider| Verdict::NeverHalts {
+                decider: decider.to_string(),
+            })
+    }
+
+    /// Classifies every machine, depth first. Verdicts in `done` are from an
+    /// earlier run and reused, the others are passed to `on_result`, so an
+    /// interrupted enumeration can be resumed from what it wrote.
+    pub fn run(
+        &self,
+        done: &HashMap<String, Verdict>,
+        mut on_result: impl FnMut(&Classified) -> io::Result<()>,
+    ) -> io::Result<Summary> {
+        let mut summary = Summary::default();
+        let mut stack = vec![Code::root(self.states, self.symbols)];
+        while let Some(code) = stack.pop() {
+            let machine = code.to_string();
+            let classified = match done.get(&machine) {
+                Some(verdict) => Classified {
+                    machine,
+                    verdict: verdict.clone(),
+                },
+                None => {
+                    let classified = Classified {
+                        verdict: self.classify(&code),
+                        machine,
+                    };
+                    on_result(&classified)?;
+                    classified
+                }
+            };
+            summary.add(&classified);
+            if let Verdict::Halts { state, symbol, .. } = &classified.verdict {
+                let state = state.as_bytes()[0] - b'A';
+                let symbol = match symbol.as_str() {
+                    "_" => 0,
+                    digit => digit.parse().unwrap(),
+                };
+                let children = code.children(state as usize, symbol);
+                stack.extend(children.into_iter().rev());
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultsError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ResultsError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ResultsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ResultsError {}
+
+/// Reads a results file written one JSON object per line, the parameters
+/// and then the results. Empty lines are skipped, an empty file has no
+/// parameters.
+pub fn read_results(
+    reader: impl BufRead,
+) -> Result<(Option<Parameters>, Vec<Classified>), ResultsError> {
+    let mut parameters = None;
+    let mut results = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line_nr = index + 1;
+        let line = line.map_err(|e| ResultsError::new(line_nr, e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if parameters.is_none() {
+            let header = serde_json::from_str(&line).map_err(|e| {
+                ResultsError::new(line_nr, format!("expected the parameters: {}", e))
+            })?;
+            parameters = Some(header);
+            continue;
+        }
+        let classified =
+            serde_json::from_str(&line).map_err(|e| ResultsError::new(line_nr, e.to_string()))?;
+        results.push(classified);
+    }
+    Ok((parameters, results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compact;
+
+    fn enumerate(states: usize, symbols: usize, steps: usize) -> (Summary, Vec<Classified>) {
+        let enumeration = Enumeration::new(states, symbols, steps)
+            .unwrap()
+            .with_default_deciders();
+        let mut results = Vec::new();
+        let summary = enumeration
+            .run(&HashMap::new(), |classified| {
+                results.push(classified.clone());
+                Ok(())
+            })
+            .unwrap();
+        (summary, results)
+    }
+
+    #[test]
+    fn busy_beavers() {
+        let (summary, results) = enumerate(2, 2, 100);
+        assert_eq!(summary.champion.unwrap().1, 6);
+        assert!(summary.undecided.is_empty());
+        assert_eq!(summary.machines, results.len());
+
+        let (summary, _) = enumerate(3, 2, 100);
+        assert_eq!(summary.champion.as_ref().unwrap().1, 21);
+        // the compact notation halts on undefined transitions too
+        let champion = compact::from_compact(&summary.champion.unwrap().0).unwrap();
+        let mut executor = DenseExecutor::new(&champion, vec![]).unwrap();
+        assert_eq!(executor.execute(&Limits::default()), RunOutcome::Accepted);
+        assert_eq!(executor.steps(), 21);
+
+        let (summary, _) = enumerate(2, 3, 200);
+        assert_eq!(summary.champion.unwrap().1, 38);
+    }
+
+    #[test]
+    fn resume() {
+        let (summary, results) = enumerate(2, 2, 100);
+        let enumeration = Enumeration::new(2, 2, 100).unwrap().with_default_deciders();
+        let mut written = Vec::new();
+        serde_json::to_writer(&mut written, &enumeration.parameters()).unwrap();
+        written.push(b'\n');
+        for classified in results[..results.len() / 2].iter() {
+            serde_json::to_writer(&mut written, classified).unwrap();
+            written.push(b'\n');
+        }
+        let (parameters, done) = read_results(&written[..]).unwrap();
+        assert_eq!(parameters, Some(enumeration.parameters()));
+        let done = done
+            .into_iter()
+            .map(|classified| (classified.machine, classified.verdict))
+            .collect::<HashMap<_, _>>();
+
+        let mut rest = Vec::new();
+        let resumed = enumeration
+            .run(&done, |classified| {
+                rest.push(classified.clone());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(resumed, summary);
+        assert_eq!(rest[..], results[results.len() / 2..]);
+
+        assert_eq!(read_results(&b""[..]).unwrap(), (None, vec![]));
+        // a file without the parameters
+        let error = read_results(&written[written.iter().position(|b| *b == b'\n').unwrap()..])
+            .unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn backward_reasoning() {
+        // halts from B reading a 1, but B is only entered from A on the
+        // left, and every step into A leaves a 0 behind
+        let machine = compact::from_compact("1RB0LA_0LA---").unwrap();
+        let decider = BackwardReasoning { depth: 10 };
+        assert_eq!(
+            decider.never_halts(&machine, 10),
+            Some("backward reasoning")
+        );
+        let champion = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        assert_eq!(decider.never_halts(&champion, 10), None);
+    }
+}
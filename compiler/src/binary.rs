@@ -0,0 +1,402 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::dot::direction_letter;
+use crate::tm::{Direction, Machine, State, TransitionFunction};
+
+/// Fixed width block code of every symbol of the original alphabet. The blank
+/// is a block of blanks, so the band of the binary machine grows with blocks
+/// that read as blank.
+#[derive(Debug, Clone)]
+pub struct BinaryEncoding {
+    pub width: usize,
+    codes: HashMap<String, String>,
+}
+
+impl BinaryEncoding {
+    fn new(alphabet: &HashSet<String>) -> Self {
+        let mut symbols = alphabet
+            .iter()
+            .filter(|s| *s != "_")
+            .cloned()
+            .collect::<Vec<_>>();
+        symbols.sort();
+        let mut width = 1;
+        while 1 << width < symbols.len() {
+            width += 1;
+        }
+        let mut codes = symbols
+            .into_iter()
+            .enumerate()
+            .map(|(index, symbol)| (symbol, format!("{:0width$b}", index, width = width)))
+            .collect::<HashMap<_, _>>();
+        codes.insert("_".to_string(), "_".repeat(width));
+        Self { width, codes }
+    }
+
+    /// The block of `width` cells representing `symbol`.
+    pub fn code(&self, symbol: &str) -> Option<&str> {
+        self.codes.get(symbol).map(String::as_str)
+    }
+
+    fn symbol(&self, code: &str) -> Option<&str> {
+        self.codes
+            .iter()
+            .find(|(_, c)| *c == code)
+            .map(|(symbol, _)| symbol.as_str())
+    }
+
+    pub fn encode_input(&self, input: &[String]) -> Option<Vec<String>> {
+        let mut encoded = Vec::with_capacity(input.len() * self.width);
+        for symbol in input {
+            encoded.extend(self.code(symbol)?.chars().map(String::from));
+        }
+        Some(encoded)
+    }
+
+    /// Decodes the band block by block, a trailing partial block is padded
+    /// with blanks. Returns `None` if a block isn't the code of any symbol.
+    pub fn decode(&self, band: &[String]) -> Option<Vec<String>> {
+        band.chunks(self.width)
+            .map(|block| {
+                let mut code = block.concat();
+                code.push_str(&"_".repeat(self.width - block.len()));
+                self.symbol(&code).map(String::from)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Phase {
+    Read {
+        state: String,
+        prefix: String,
+    },
+    Write {
+        state: String,
+        direction: Direction,
+        code: String,
+        bit: usize,
+    },
+    Move {
+        state: String,
+        direction: Direction,
+        remaining: usize,
+    },
+    End(String),
+}
+
+impl Phase {
+    fn name(&self) -> String {
+        match self {
+            Phase::Read { state, prefix } => format!("read {} {}", state, prefix),
+            Phase::Write {
+                state,
+                direction,
+                code,
+                bit,
+            } => format!(
+                "write {} {} {} {}",
+                state,
+                direction_letter(direction),
+                code,
+                bit
+            ),
+            Phase::Move {
+                state,
+                direction,
+                remaining,
+            } => format!(
+                "move {} {} {}",
+                state,
+                direction_letter(direction),
+                remaining
+            ),
+            Phase::End(state) => format!("end {}", state),
+        }
+    }
+}
+
+fn start(machine: &Machine, state: &str) -> Phase {
+    if machine.states[state].is_end_state {
+        Phase::End(state.to_string())
+    } else {
+        Phase::Read {
+            state: state.to_string(),
+            prefix: String::new(),
+        }
+    }
+}
+
+/// Where the head goes after writing the first cell of a block, for `width`
+/// cell blocks.
+fn after_block(
+    machine: &Machine,
+    state: &str,
+    direction: &Direction,
+    width: usize,
+) -> (Direction, Phase) {
+    match direction {
+        Direction::Unchanged => (Direction::Unchanged, start(machine, state)),
+        direction if width == 1 => (direction.clone(), start(machine, state)),
+        direction => (
+            direction.clone(),
+            Phase::Move {
+                state: state.to_string(),
+                direction: direction.clone(),
+                remaining: width - 1,
+            },
+        ),
+    }
+}
+
+/**
+ * Re-encodes a single band machine over the alphabet {0, 1, _}
+ *
+ * every symbol becomes a block of `width` cells holding its index in the sorted
+ * alphabet in binary, the blank becomes a block of blanks. the binary machine
+ * keeps its head on the first cell of a block and simulates each step by
+ * - reading the block left to right, remembering the bits in its state
+ * - writing the code of the new symbol right to left
+ * - moving a whole block in the direction of the transition
+ *
+ * moving a block left from the first block behaves like the band of the
+ * original machine, so the binary machine uses the same tape mode.
+ *
+ * use `BinaryEncoding::encode_input` on the input and `BinaryEncoding::decode`
+ * on the resulting band. machines with more than one band can be re-encoded
+ * after `single_tape::to_single_tape`.
+ */
+pub fn to_binary(machine: &Machine) -> Result<(Machine, BinaryEncoding), &'static str> {
+    if machine.size != 1 {
+        return Err("only single band machines can be re-encoded");
+    }
+    let machine = &machine.expand_wildcards();
+    let encoding = BinaryEncoding::new(&machine.alphabet);
+    let width = encoding.width;
+
+    let mut states = HashMap::new();
+    let mut queue = VecDeque::from([start(machine, &machine.start_state_name)]);
+    while let Some(phase) = queue.pop_front() {
+        let name = phase.name();
+        if states.contains_key(&name) {
+            continue;
+        }
+
+        let mut transitions: Vec<(&str, String, Direction, Phase)> = Vec::new();
+        match &phase {
+            Phase::Read { state, prefix } => {
+                let readable = if prefix.is_empty() {
+                    vec!["0", "1", "_"]
+                } else if prefix.starts_with('_') {
+                    vec!["_"]
+                } else {
+                    vec!["0", "1"]
+                };
+                for read in readable {
+                    let prefix = format!("{}{}", prefix, read);
+                    if prefix.len() < width {
+                        transitions.push((
+                            read,
+                            read.to_string(),
+                            Direction::Right,
+                            Phase::Read {
+                                state: state.clone(),
+                                prefix,
+                            },
+                        ));
+                        continue;
+                    }
+
+                    let symbol = encoding.symbol(&prefix);
+                    let f = machine.states[state]
+                        .transition_functions
+                        .iter()
+                        .find(|f| Some(f.bands_requirements[0].as_str()) == symbol);
+                    let f = match f {
+                        Some(f) => f,
+                        None => continue,
+                    };
+                    let (write, direction) = &f.bands_actions[0];
+                    let code = encoding.code(write).unwrap().to_string();
+                    let last = code[width - 1..].to_string();
+                    if width == 1 {
+                        let (direction, next) =
+                            after_block(machine, &f.next_state_name, direction, width);
+                        transitions.push((read, last, direction, next));
+                    } else {
+                        transitions.push((
+                            read,
+                            last,
+                            Direction::Left,
+                            Phase::Write {
+                                state: f.next_state_name.clone(),
+                                direction: direction.clone(),
+                                code,
+                                bit: width - 2,
+                            },
+                        ));
+                    }
+                }
+            }
+            Phase::Write {
+                state,
+                direction,
+                code,
+                bit,
+            } => {
+                let written = code[*bit..*bit + 1].to_string();
+                let (direction, next) = if *bit == 0 {
+                    after_block(machine, state, direction, width)
+                } else {
+                    (
+                        Direction::Left,
+                        Phase::Write {
+                            state: state.clone(),
+                            direction: direction.clone(),
+                            code: code.clone(),
+                            bit: bit - 1,
+                        },
+                    )
+                };
+                for read in ["0", "1", "_"] {
+                    transitions.push((read, written.clone(), direction.clone(), next.clone()));
+                }
+            }
+            Phase::Move {
+                state,
+                direction,
+                remaining,
+            } => {
+                let next = if *remaining == 1 {
+                    start(machine, state)
+                } else {
+                    Phase::Move {
+                        state: state.clone(),
+                        direction: direction.clone(),
+                        remaining: remaining - 1,
+                    }
+                };
+                for read in ["0", "1", "_"] {
+                    transitions.push((read, read.to_string(), direction.clone(), next.clone()));
+                }
+            }
+            Phase::End(_) => {}
+        }
+
+        let transition_functions = transitions
+            .into_iter()
+            .map(|(read, write, direction, next)| {
+                let function = TransitionFunction::new(
+                    name.clone(),
+                    vec![read.to_string()],
+                    vec![(write, direction)],
+                    next.name(),
+                );
+                queue.push_back(next);
+                function
+            })
+            .collect();
+        let is_end_state = matches!(phase, Phase::End(_));
+        states.insert(name, State::new(transition_functions, is_end_state));
+    }
+
+    let binary = Machine::new(
+        HashSet::from(["0".to_string(), "1".to_string()]),
+        1,
+        states,
+        start(machine, &machine.start_state_name).name(),
+    )?
+    .with_tape_mode(machine.tape_mode);
+    Ok((binary, encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::sim::{Limits, MachineExecutor, RunOutcome, Simulator};
+    use crate::test_util::random_machine;
+    use crate::tm::TapeMode;
+
+    const SYMBOLS: [&str; 7] = ["_", "1", "1'", "2", "3", "4", "5"];
+
+    fn trimmed(band: &[String]) -> Vec<String> {
+        let end = band.iter().rposition(|s| s != "_").map_or(0, |i| i + 1);
+        let start = band.iter().position(|s| s != "_").unwrap_or(end);
+        band[start..end].to_vec()
+    }
+
+    #[test]
+    fn simulates_the_original_machine() {
+        let mut random = Random::new(0x2545_f491_4f6c_dd1d);
+        let mut compared = 0;
+        for mode in [
+            TapeMode::Clamped,
+            TapeMode::LeftBounded,
+            TapeMode::TwoWayInfinite,
+        ] {
+            for _ in 0..40 {
+                let machine = random_machine(&mut random, &SYMBOLS, 1, mode);
+                let (binary, encoding) = to_binary(&machine).unwrap();
+                for _ in 0..5 {
+                    let input = (0..random.below(6))
+                        .map(|_| SYMBOLS[1 + random.below(SYMBOLS.len() - 1)].to_string())
+                        .collect::<Vec<_>>();
+                    let mut original = MachineExecutor::new(machine.clone(), input.clone());
+                    let outcome = original.run(&Limits {
+                        steps: Some(50),
+                        ..Limits::default()
+                    });
+                    if outcome == RunOutcome::StepLimitExceeded {
+                        continue;
+                    }
+                    let encoded = encoding.encode_input(&input).unwrap();
+                    let mut simulation = MachineExecutor::new(binary.clone(), encoded);
+                    let simulated = simulation.run(&Limits {
+                        steps: Some(100_000),
+                        ..Limits::default()
+                    });
+                    compared += 1;
+                    match &outcome {
+                        RunOutcome::Rejected { .. } => {
+                            assert!(matches!(simulated, RunOutcome::Rejected { .. }))
+                        }
+                        outcome => assert_eq!(&simulated, outcome),
+                    }
+                    // falling off leaves the band in the middle of a step
+                    if matches!(outcome, RunOutcome::FellOffTape { .. }) {
+                        continue;
+                    }
+                    let band = encoding.decode(&simulation.band_symbols()[0]).unwrap();
+                    assert_eq!(trimmed(&band), trimmed(&original.band_symbols()[0]));
+                }
+            }
+        }
+        assert!(compared > 300, "only {} runs halted", compared);
+    }
+
+    #[test]
+    fn block_codes_round_trip() {
+        let alphabet = ["1", "1'", "2", "3", "4", "5", "_"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<HashSet<_>>();
+        let encoding = BinaryEncoding::new(&alphabet);
+        assert_eq!(encoding.width, 3);
+        assert_eq!(encoding.code("1"), Some("000"));
+        assert_eq!(encoding.code("_"), Some("___"));
+
+        let input = ["1'", "5", "_", "2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let encoded = encoding.encode_input(&input).unwrap();
+        assert_eq!(encoded.len(), 12);
+        assert_eq!(encoding.decode(&encoded).unwrap(), input);
+
+        let mut grown = encoded;
+        grown.push("_".to_string());
+        assert_eq!(encoding.decode(&grown).unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::tm::{Direction, Machine, State, TapeMode, TransitionFunction};
+
+/// Name of the end state emitted for halting transitions.
+pub const HALT_STATE: &str = "Z";
+
+const UNDEFINED: &str = "---";
+
+pub(crate) fn state_letter(index: usize) -> Option<char> {
+    (index < 25).then(|| (b'A' + index as u8) as char)
+}
+
+pub(crate) fn symbol_name(digit: u32) -> String {
+    if digit == 0 {
+        "_".to_string()
+    } else {
+        digit.to_string()
+    }
+}
+
+/**
+ * Parses the compact notation used in the busy beaver community
+ * each state is a group of transitions separated by '_', one transition per symbol
+ * a transition is the symbol to write, the direction (L or R) and the next state
+ * states are named A, B, C, ... and any letter after the last state halts
+ * '---' marks an undefined transition, which halts without writing or moving
+ *
+ * example (the two state busy beaver champion):
+ * 1RB1LB_1LA1RZ
+ *
+ * symbol 0 is the blank and becomes '_', the other symbols keep their digit
+ * the band is two-way infinite like in the busy beaver literature
+ */
+pub fn from_compact(s: &str) -> Result<Machine, &'static str> {
+    let segments = s.trim().split('_').collect::<Vec<_>>();
+    let symbols = segments[0].len() / 3;
+    if !(2..=10).contains(&symbols) {
+        return Err("expected between 2 and 10 symbols");
+    }
+    if state_letter(segments.len() - 1).is_none() {
+        return Err("too many states");
+    }
+
+    let alphabet = (0..symbols as u32).map(symbol_name).collect::<HashSet<_>>();
+    let mut states = HashMap::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let segment = segment.as_bytes();
+        if segment.len() != symbols * 3 {
+            return Err("every state needs a transition for each symbol");
+        }
+        let origin = state_letter(index).unwrap().to_string();
+        let mut transition_functions = Vec::new();
+        for (digit, transition) in segment.chunks(3).enumerate() {
+            let read = symbol_name(digit as u32);
+            if transition == UNDEFINED.as_bytes() {
+                transition_functions.push(TransitionFunction::new(
+                    origin.clone(),
+                    vec![read.clone()],
+                    vec![(read, Direction::Unchanged)],
+                    HALT_STATE.to_string(),
+                ));
+                continue;
+            }
+            let write = (transition[0] as char)
+                .to_digit(10)
+                .filter(|d| (*d as usize) < symbols)
+                .ok_or("symbol to write is not in the alphabet")?;
+            let direction = match transition[1].to_ascii_uppercase() {
+                b'L' => Direction::Left,
+                b'R' => Direction::Right,
+                _ => return Err("direction must be L or R"),
+            };
+            let next = transition[2].to_ascii_uppercase();
+            if !next.is_ascii_uppercase() {
+                return Err("next state must be a letter");
+            }
+            transition_functions.push(TransitionFunction::new(
+                origin.clone(),
+                vec![read],
+                vec![(symbol_name(write), direction)],
+                (next as char).to_string(),
+            ));
+        }
+        states.insert(origin, State::new(transition_functions, false));
+    }
+
+    let halting = states
+        .values()
+        .flat_map(|state| state.transition_functions.iter())
+        .map(|f| f.next_state_name.clone())
+        .filter(|name| !states.contains_key(name))
+        .collect::<HashSet<_>>();
+    for name in halting {
+        states.insert(name, State::new(vec![], true));
+    }
+
+    Ok(
+        Machine::new(alphabet, 1, states, "A".to_string())?
+            .with_tape_mode(TapeMode::TwoWayInfinite),
+    )
+}
+
+/// Emits a single band machine in compact notation. The start state becomes
+/// `A` and the others are lettered in breadth first order, transitions into
+/// end states go to `Z`. The blank is symbol 0 and the remaining symbols are
+/// numbered in sorted order, so symbol names other than digits are not
+/// preserved. A transition that keeps the symbol and head in place on its way
+/// to an end state, as well as a missing transition, is written as `---`.
+pub fn to_compact(machine: &Machine) -> Result<String, &'static str> {
+    if machine.size != 1 {
+        return Err("only single band machines can be written in compact notation");
+    }
+    let machine = &machine.expand_wildcards();
+    let mut symbols = machine
+        .alphabet
+        .iter()
+        .map(String::as_str)
+        .filter(|s| *s != "_")
+        .collect::<Vec<_>>();
+    symbols.sort_unstable();
+    symbols.insert(0, "_");
+    if symbols.len() > 10 {
+        return Err("compact notation supports at most 10 symbols");
+    }
+
+    let mut order: Vec<&str> = Vec::new();
+    let mut queue = VecDeque::from([machine.start_state_name.as_str()]);
+    while let Some(name) = queue.pop_front() {
+        if order.contains(&name) || machine.states[name].is_end_state {
+            continue;
+        }
+        order.push(name);
+        queue.extend(
+            machine.states[name]
+                .transition_functions
+                .iter()
+                .map(|f| f.next_state_name.as_str()),
+        );
+    }
+    if order.is_empty() {
+        return Err("start state is an end state");
+    }
+    let letters = order
+        .iter()
+        .enumerate()
+        .map(|(index, name)| state_letter(index).map(|l| (*name, l)))
+        .collect::<Option<HashMap<_, _>>>()
+        .ok_or("too many states")?;
+
+    let mut segments = Vec::new();
+    for name in order.iter() {
+        let mut segment = String::new();
+        for symbol in symbols.iter() {
+            let function = machine.states[*name]
+                .transition_functions
+                .iter()
+                .find(|f| f.bands_requirements[0] == **symbol);
+            let f = match function {
+                Some(f) => f,
+                None => {
+                    segment.push_str(UNDEFINED);
+                    continue;
+                }
+            };
+            let (write, direction) = &f.bands_actions[0];
+            let halts = machine.states[&f.next_state_name].is_end_state;
+            if halts && write == *symbol && matches!(direction, Direction::Unchanged) {
+                segment.push_str(UNDEFINED);
+                continue;
+            }
+            let digit = symbols.iter().position(|s| *s == write).unwrap();
+            segment.push_str(&digit.to_string());
+            segment.push(match direction {
+                Direction::Left => 'L',
+                Direction::Right => 'R',
+                Direction::Unchanged => {
+                    return Err("compact notation can't express a transition without movement")
+                }
+            });
+            segment.push(if halts {
+                HALT_STATE.chars().next().unwrap()
+            } else {
+                letters[f.next_state_name.as_str()]
+            });
+        }
+        segments.push(segment);
+    }
+
+    Ok(segments.join("_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_champion() {
+        let machine = from_compact("1RB1LB_1LA1RZ").unwrap();
+        assert_eq!(machine.start_state_name, "A");
+        assert_eq!(machine.states.len(), 3);
+        assert!(machine.states["Z"].is_end_state);
+        let f = &machine.states["B"].transition_functions[0];
+        assert_eq!(f.bands_requirements, vec!["_"]);
+        assert_eq!(f.bands_actions[0].0, "1");
+        assert_eq!(f.next_state_name, "A");
+    }
+
+    #[test]
+    fn round_trip() {
+        for s in ["1RB1LB_1LA1RZ", "1RB2LB1RZ_2LA2RB1LB", "1RB---_0LA1RB"] {
+            assert_eq!(to_compact(&from_compact(s).unwrap()).unwrap(), s);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!(from_compact("1RB1LB_1LA").is_err());
+        assert!(from_compact("1XB1LB_1LA1RZ").is_err());
+        assert!(from_compact("2RB1LB_1LA1RZ").is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+
+use crate::ast::SourceMap;
+use crate::sim::{Limits, MachineExecutor, RunOutcome, Simulator};
+use crate::tm::Machine;
+
+/// What the statements on one source line did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineCoverage {
+    /// How often a statement on the line started.
+    pub hits: usize,
+    /// Every alternative of the statements on the line with how often it was
+    /// taken, by statement.
+    pub alternatives: Vec<Vec<(String, usize)>>,
+}
+
+impl LineCoverage {
+    /// Alternatives that were never taken.
+    pub fn missed(&self) -> Vec<&str> {
+        self.alternatives
+            .iter()
+            .flatten()
+            .filter(|(_, taken)| *taken == 0)
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+}
+
+/// Adds up which statements of a compiled program ran over a set of inputs.
+pub struct Coverage {
+    machine: Machine,
+    source_map: SourceMap,
+    state_visits: BTreeMap<String, usize>,
+    transition_firings: BTreeMap<String, Vec<usize>>,
+}
+
+impl Coverage {
+    pub fn new(machine: Machine, source_map: SourceMap) -> Self {
+        Self {
+            state_visits: machine
+                .states
+                .keys()
+                .map(|name| (name.clone(), 0))
+                .collect(),
+            transition_firings: machine
+                .states
+                .iter()
+                .map(|(name, state)| (name.clone(), vec![0; state.transition_functions.len()]))
+                .collect(),
+            machine,
+            source_map,
+        }
+    }
+
+    /// Runs the machine on `input` and adds the run to the coverage.
+    pub fn record(&mut self, input: Vec<String>, limits: &Limits) -> RunOutcome {
+        let mut executor = MachineExecutor::new(self.machine.clone(), input);
+        executor.collect_statistics();
+        let outcome = executor.run(limits);
+        let statistics = executor.statistics().unwrap();
+        for (state, visits) in statistics.state_visits.iter() {
+            *self.state_visits.get_mut(state).unwrap() += visits;
+        }
+        for (state, firings) in statistics.transition_firings.iter() {
+            let total = self.transition_firings.get_mut(state).unwrap();
+            for (total, firings) in total.iter_mut().zip(firings) {
+                *total += firings;
+            }
+        }
+        outcome
+    }
+
+    /// The coverage of every line with a statement.
+    pub fn lines(&self) -> BTreeMap<usize, LineCoverage> {
+        let mut statements = self.source_map.statements.iter().collect::<Vec<_>>();
+        statements.sort_by_key(|(state, source)| (source.line, *state));
+
+        let mut lines = BTreeMap::<usize, LineCoverage>::new();
+        for (state, source) in statements {
+            let mut taken = vec![0; source.alternatives.len()];
+            for (alternative, firings) in
+                source.functions.iter().zip(&self.transition_firings[state])
+            {
+                taken[*alternative] += firings;
+            }
+            let line = lines.entry(source.line).or_default();
+            line.hits += self.state_visits[state];
+            line.alternatives
+                .push(source.alternatives.iter().cloned().zip(taken).collect());
+        }
+        lines
+    }
+
+    /// The coverage as an lcov tracefile for `path`, every statement is a
+    /// block and every alternative a branch of it.
+    pub fn to_lcov(&self, test_name: &str, path: &str) -> String {
+        let lines = self.lines();
+        let mut out = format!("TN:{}\nSF:{}\n", test_name, path);
+        let (mut branches, mut branches_hit) = (0, 0);
+        for (number, line) in lines.iter() {
+            for (block, alternatives) in line.alternatives.iter().enumerate() {
+                for (branch, (_, taken)) in alternatives.iter().enumerate() {
+                    let taken = if line.hits == 0 {
+                        "-".to_string()
+                    } else {
+                        taken.to_string()
+                    };
+                    out.push_str(&format!("BRDA:{},{},{},{}\n", number, block, branch, taken));
+                }
+                branches += alternatives.len();
+                branches_hit += alternatives.iter().filter(|(_, taken)| *taken > 0).count();
+            }
+        }
+        out.push_str(&format!("BRF:{}\nBRH:{}\n", branches, branches_hit));
+        for (number, line) in lines.iter() {
+            out.push_str(&format!("DA:{},{}\n", number, line.hits));
+        }
+        out.push_str(&format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            lines.len(),
+            lines.values().filter(|line| line.hits > 0).count()
+        ));
+        out
+    }
+
+    /// `source` as an HTML page, lines that never ran are red and lines with
+    /// alternatives that were never taken yellow, hovering them lists those.
+    pub fn to_html(&self, title: &str, source: &str) -> String {
+        let lines = self.lines();
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>\n\
+             pre {{ font-family: monospace; }}\n\
+             .hit {{ background: #d4f7d4; }}\n\
+             .partial {{ background: #fff3b0; }}\n\
+             .missed {{ background: #f7d4d4; }}\n\
+             </style>\n</head>\n<body>\n<pre>\n",
+            escape_html(title)
+        );
+        for (index, text) in source.lines().enumerate() {
+            let number = index + 1;
+            let (class, hits, missed) = match lines.get(&number) {
+                None => {
+                    out.push_str(&format!("{:>5}        {}\n", number, escape_html(text)));
+                    continue;
+                }
+                Some(line) if line.hits == 0 => ("missed", 0, vec![]),
+                Some(line) => {
+                    let missed = line.missed();
+                    let class = if missed.is_empty() { "hit" } else { "partial" };
+                    (class, line.hits, missed)
+                }
+            };
+            let title = if missed.is_empty() {
+                String::new()
+            } else {
+                format!(" title=\"not taken: {}\"", escape_html(&missed.join(" | ")))
+            };
+            out.push_str(&format!(
+                "<span class=\"{}\"{}>{:>5} {:>6} {}</span>\n",
+                class,
+                title,
+                number,
+                hits,
+                escape_html(text)
+            ));
+        }
+        out.push_str("</pre>\n</body>\n</html>\n");
+        out
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::*;
+    use crate::parser;
+
+    fn step(cases: Vec<(Vec<&str>, Option<&str>, TmDir)>, default: Option<TmOperation>) -> TmStep {
+        let cases = cases
+            .into_iter()
+            .map(|(patterns, replace, direction)| {
+                AtomicTmStep::new(
+                    patterns.into_iter().map(String::from).collect(),
+                    replace.map(String::from),
+                    direction,
+                )
+            })
+            .collect();
+        TmStep::new(cases, default)
+    }
+
+    /// Leaves an input starting with `b` alone, otherwise replaces every `a`
+    /// with `b`.
+    fn program() -> TmDef {
+        let alphabet = ["a", "b"].iter().map(|s| s.to_string()).collect();
+        TmDef::new(
+            "replace".to_string(),
+            alphabet,
+            TmBlock::new(vec![
+                TmStmt::Branch {
+                    condition: step(vec![(vec!["b"], None, TmDir::Stay)], None).at_line(2),
+                    body: TmBlock::new(vec![]),
+                },
+                TmStmt::Cycle(TmBlock::new(vec![TmStmt::Step(
+                    step(
+                        vec![
+                            (vec!["a"], Some("b"), TmDir::Right),
+                            (vec!["b"], None, TmDir::Right),
+                        ],
+                        Some(TmOperation::Break),
+                    )
+                    .at_line(3),
+                )])),
+            ]),
+        )
+    }
+
+    #[test]
+    fn alternatives_and_branches() {
+        let (machine, source_map) = into_machine(program()).unwrap();
+        let mut coverage = Coverage::new(machine, source_map);
+        let input = |s: &str| s.chars().map(|c| c.to_string()).collect();
+        assert_eq!(
+            coverage.record(input("aa"), &Limits::default()),
+            RunOutcome::Accepted
+        );
+
+        let lines = coverage.lines();
+        assert_eq!(lines[&2].hits, 1);
+        // the branch was skipped, but never taken
+        assert_eq!(lines[&2].missed(), vec!["b"]);
+        // two `a`s and the blank at the end
+        assert_eq!(lines[&3].hits, 3);
+        assert_eq!(lines[&3].missed(), vec!["b"]);
+        assert!(coverage
+            .to_lcov("replace", "replace.tm")
+            .contains("BRDA:3,0,1,0\n"));
+
+        assert_eq!(
+            coverage.record(input("b"), &Limits::default()),
+            RunOutcome::Accepted
+        );
+        assert!(coverage.lines()[&2].missed().is_empty());
+
+        let html = coverage.to_html("replace", "fn replace [a, b] {\n    branch\n    cycle\n}");
+        assert!(html.contains("<span class=\"hit\">    2"));
+        assert!(html.contains("<span class=\"partial\" title=\"not taken: b\">    3"));
+    }
+
+    #[test]
+    fn multiply() {
+        let def = parser::parse_def(include_str!("../test.tm")).unwrap();
+        let (machine, source_map) = into_machine(def).unwrap();
+        let mut coverage = Coverage::new(machine, source_map);
+        for input in ["112", "1122"] {
+            let input = input.chars().map(|c| c.to_string()).collect();
+            assert_eq!(
+                coverage.record(input, &Limits::default()),
+                RunOutcome::Accepted
+            );
+        }
+
+        let lcov = coverage.to_lcov("multiply", "test.tm");
+        // neither input starts with a 2, ends with a 1 or makes it to the
+        // halt in the middle of the cycle
+        for line in [3, 11, 12, 28, 33, 46] {
+            assert!(lcov.contains(&format!("\nDA:{},0\n", line)));
+        }
+        assert!(lcov.contains("\nBRDA:3,0,0,-\n"));
+        assert!(lcov.contains("\nDA:7,7\n"));
+        assert!(lcov.contains("\nLF:27\nLH:15\n"));
+    }
+}
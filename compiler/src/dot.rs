@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::tm::{Direction, Machine, TransitionFunction};
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub(crate) fn direction_letter(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::Right => "R",
+        Direction::Left => "L",
+        Direction::Unchanged => "N",
+    }
+}
+
+/// Label of a group of transitions sharing origin and target, one line per
+/// distinct action, e.g. `0,1 → 1,R`. Bands are separated by `/`, symbols are
+/// passed through `symbol` so other exporters can escape them.
+pub(crate) fn edge_label(
+    functions: &[&TransitionFunction],
+    symbol: impl Fn(&str) -> String,
+    arrow: &str,
+) -> Vec<String> {
+    let mut by_action: Vec<(String, Vec<String>)> = Vec::new();
+    for f in functions {
+        let action = f
+            .bands_actions
+            .iter()
+            .map(|(s, direction)| format!("{},{}", symbol(s), direction_letter(direction)))
+            .collect::<Vec<_>>()
+            .join("/");
+        let requirement = f
+            .bands_requirements
+            .iter()
+            .map(|s| symbol(s))
+            .collect::<Vec<_>>()
+            .join("/");
+        match by_action.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, requirements)) => requirements.push(requirement),
+            None => by_action.push((action, vec![requirement])),
+        }
+    }
+    by_action
+        .into_iter()
+        .map(|(action, requirements)| format!("{} {} {}", requirements.join(","), arrow, action))
+        .collect()
+}
+
+/// Transitions of a state grouped by their target, in order of first use.
+pub(crate) fn parallel_edges<'a>(
+    machine: &'a Machine,
+    name: &str,
+) -> Vec<(&'a str, Vec<&'a TransitionFunction>)> {
+    let mut edges: Vec<(&str, Vec<&TransitionFunction>)> = Vec::new();
+    for f in machine.states[name].transition_functions.iter() {
+        match edges
+            .iter_mut()
+            .find(|(next, _)| *next == f.next_state_name)
+        {
+            Some((_, functions)) => functions.push(f),
+            None => edges.push((&f.next_state_name, vec![f])),
+        }
+    }
+    edges
+}
+
+/// Renders the machine as a graphviz digraph. If `clusters` maps state names
+/// to the DSL block they were generated from, states of the same block are
+/// grouped into a cluster labelled with the block name.
+pub fn to_dot(machine: &Machine, clusters: Option<&HashMap<String, String>>) -> String {
+    let mut names = machine.states.keys().collect::<Vec<_>>();
+    names.sort();
+
+    let mut out = String::from("digraph {\n    rankdir=LR;\n");
+    out.push_str("    __start [shape=point];\n");
+
+    let node = |name: &str| {
+        let shape = if machine.states[name].is_end_state {
+            "doublecircle"
+        } else {
+            "circle"
+        };
+        format!("\"{}\" [shape={}];\n", escape(name), shape)
+    };
+
+    let mut grouped: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+    for name in names.iter() {
+        match clusters.and_then(|c| c.get(*name)) {
+            Some(block) => grouped.entry(block).or_default().push(name),
+            None => out.push_str(&format!("    {}", node(name))),
+        }
+    }
+    for (index, (block, members)) in grouped.into_iter().enumerate() {
+        out.push_str(&format!(
+            "    subgraph cluster_{} {{\n        label=\"{}\";\n",
+            index,
+            escape(block)
+        ));
+        for name in members {
+            out.push_str(&format!("        {}", node(name)));
+        }
+        out.push_str("    }\n");
+    }
+
+    out.push_str(&format!(
+        "    __start -> \"{}\";\n",
+        escape(&machine.start_state_name)
+    ));
+    for name in names {
+        for (next, functions) in parallel_edges(machine, name) {
+            out.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                escape(name),
+                escape(next),
+                edge_label(&functions, escape, "→").join("\\n")
+            ));
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+    use crate::tm::State;
+
+    #[test]
+    fn groups_parallel_transitions() {
+        let mut states = HashMap::new();
+        states.insert(
+            "q0".to_string(),
+            State::new(
+                vec![
+                    TransitionFunction::new(
+                        "q0".to_string(),
+                        vec!["0".to_string()],
+                        vec![("1".to_string(), Direction::Right)],
+                        "q0".to_string(),
+                    ),
+                    TransitionFunction::new(
+                        "q0".to_string(),
+                        vec!["1".to_string()],
+                        vec![("1".to_string(), Direction::Right)],
+                        "q0".to_string(),
+                    ),
+                    TransitionFunction::new(
+                        "q0".to_string(),
+                        vec!["_".to_string()],
+                        vec![("_".to_string(), Direction::Unchanged)],
+                        "q1".to_string(),
+                    ),
+                ],
+                false,
+            ),
+        );
+        states.insert("q1".to_string(), State::new(vec![], true));
+        let machine = Machine::new(
+            HashSet::from(["0".to_string(), "1".to_string()]),
+            1,
+            states,
+            "q0".to_string(),
+        )
+        .unwrap();
+
+        let dot = to_dot(&machine, None);
+        assert!(dot.contains("\"q0\" -> \"q0\" [label=\"0,1 → 1,R\"];"));
+        assert!(dot.contains("\"q0\" -> \"q1\" [label=\"_ → _,N\"];"));
+        assert!(dot.contains("\"q1\" [shape=doublecircle];"));
+        assert!(dot.contains("__start -> \"q0\";"));
+    }
+}
@@ -0,0 +1,285 @@
+use std::fmt;
+
+use crate::random::Random;
+use crate::sim::{Limits, MachineExecutor, RunOutcome};
+use crate::tm::Machine;
+
+/// How a machine ended on an input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    pub outcome: RunOutcome,
+    /// The first band without the blanks at both ends.
+    pub band: Vec<String>,
+}
+
+/// An input the two machines don't agree on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub input: Vec<String>,
+    pub left: Run,
+    pub right: Run,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "input: {}", self.input.join(" "))?;
+        for (side, run) in [("left", &self.left), ("right", &self.right)] {
+            writeln!(
+                f,
+                "{}: {:?}, band: {}",
+                side,
+                run.outcome,
+                run.band.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What `Equivalence::check` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Number of inputs both machines were run on.
+    pub inputs: usize,
+    /// Inputs on which a run hit a limit, so they weren't compared.
+    pub inconclusive: Vec<Vec<String>>,
+    /// The first input the machines don't agree on, shorter inputs first.
+    pub difference: Option<Difference>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.difference {
+            Some(difference) => write!(f, "{}", difference)?,
+            None => writeln!(f, "no difference")?,
+        }
+        writeln!(f, "inputs: {}", self.inputs)?;
+        writeln!(f, "inconclusive: {}", self.inconclusive.len())
+    }
+}
+
+/// Whether two outcomes are the same, `None` if one of the runs hit a
+/// limit. Runs that never halt are the same however that was proven.
+fn same_outcome(left: &RunOutcome, right: &RunOutcome) -> Option<bool> {
+    let kind = |outcome: &RunOutcome| match outcome {
+        RunOutcome::Accepted => Some(0),
+        RunOutcome::Rejected { .. } => Some(1),
+        RunOutcome::FellOffTape { .. } => Some(2),
+        RunOutcome::Cycle { .. } | RunOutcome::TranslatedCycle { .. } => Some(3),
+        RunOutcome::StepLimitExceeded
+        | RunOutcome::TapeLimitExceeded
+        | RunOutcome::TimeLimitExceeded
+        | RunOutcome::Breakpoint { .. } => None,
+    };
+    Some(kind(left)? == kind(right)?)
+}
+
+/// Advances `input`, digits below `symbols`, to the next input of the same
+/// length with the last digit fastest. `false` after the last one.
+fn next_input(input: &mut [usize], symbols: usize) -> bool {
+    for digit in input.iter_mut().rev() {
+        *digit += 1;
+        if *digit < symbols {
+            return true;
+        }
+        *digit = 0;
+    }
+    false
+}
+
+/// Compares two machines on every input up to a length, and optionally on
+/// random longer inputs. The machines agree on an input if both accept, both
+/// reject, both fall off the band or both never halt, and the first band is
+/// the same up to blanks at the ends when they halt. Head positions and the
+/// other bands aren't compared.
+pub struct Equivalence {
+    alphabet: Vec<String>,
+    max_length: usize,
+    limits: Limits,
+    /// Number of random inputs, their maximum length and the seed.
+    random: Option<(usize, usize, u64)>,
+}
+
+impl Equivalence {
+    pub fn new(alphabet: Vec<String>, max_length: usize) -> Self {
+        Self {
+            alphabet,
+            max_length,
+            limits: Limits {
+                steps: Some(100_000),
+                ..Limits::default()
+            },
+            random: None,
+        }
+    }
+
+    /// Budgets of every run, runs that exceed them are inconclusive.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// After the exhaustive inputs, compares `count` random inputs longer than
+    /// those and at most `max_length` long. The same seed gives the same
+    /// inputs.
+    pub fn with_random_inputs(mut self, count: usize, max_length: usize, seed: u64) -> Self {
+        self.random = Some((count, max_length, seed));
+        self
+    }
+
+    fn run(&self, machine: &Machine, input: &[String]) -> Run {
+        let mut executor = MachineExecutor::new(machine.clone(), input.to_vec());
+        let outcome = executor.run_detecting_cycles(&self.limits, |_| {});
+        let mut band = executor.bands()[0].symbols();
+        let end = band.iter().rposition(|s| s != "_").map_or(0, |i| i + 1);
+        band.truncate(end);
+        let start = band.iter().position(|s| s != "_").unwrap_or(0);
+        band.drain(..start);
+        Run { outcome, band }
+    }
+
+    pub fn check(&self, left: &Machine, right: &Machine) -> Result<Report, &'static str> {
+        if self
+            .alphabet
+            .iter()
+            .any(|s| !left.alphabet.contains(s) || !right.alphabet.contains(s))
+        {
+            return Err("input symbol not in the alphabet of both machines");
+        }
+
+        let mut report = Report {
+            inputs: 0,
+            inconclusive: Vec::new(),
+            difference: None,
+        };
+        let mut compare = |input: Vec<String>| {
+            let (left, right) = (self.run(left, &input), self.run(right, &input));
+            report.inputs += 1;
+            match same_outcome(&left.outcome, &right.outcome) {
+                None => report.inconclusive.push(input),
+                Some(same) => {
+                    let halted = !matches!(
+                        left.outcome,
+                        RunOutcome::Cycle { .. } | RunOutcome::TranslatedCycle { .. }
+                    );
+                    if !same || (halted && left.band != right.band) {
+                        report.difference = Some(Difference { input, left, right });
+                        return false;
+                    }
+                }
+            }
+            true
+        };
+
+        let symbols = self.alphabet.len();
+        for length in 0..=self.max_length {
+            if symbols == 0 && length > 0 {
+                break;
+            }
+            let mut digits = vec![0; length];
+            loop {
+                let input = digits.iter().map(|d| self.alphabet[*d].clone()).collect();
+                if !compare(input) {
+                    return Ok(report);
+                }
+                if !next_input(&mut digits, symbols) {
+                    break;
+                }
+            }
+        }
+
+        if let Some((count, max_length, seed)) = self.random {
+            if symbols == 0 || max_length <= self.max_length {
+                return Ok(report);
+            }
+            let mut random = Random::new(seed);
+            for _ in 0..count {
+                let length = self.max_length + 1 + random.below(max_length - self.max_length);
+                let input = (0..length)
+                    .map(|_| self.alphabet[random.below(symbols)].clone())
+                    .collect();
+                if !compare(input) {
+                    break;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmsim;
+
+    const INCREMENT: &str = "init: right\naccept: done\n\
+        right,0\nright,0,>\nright,1\nright,1,>\nright,_\ncarry,_,<\n\
+        carry,1\ncarry,0,<\ncarry,0\ndone,1,-\ncarry,_\ndone,1,-";
+
+    fn alphabet() -> Vec<String> {
+        vec!["0".to_string(), "1".to_string()]
+    }
+
+    #[test]
+    fn equivalent() {
+        // walks right in two states taking turns
+        let machine = tmsim::from_tmsim(
+            "init: even\naccept: done\n\
+             even,0\nodd,0,>\neven,1\nodd,1,>\neven,_\ncarry,_,<\n\
+             odd,0\neven,0,>\nodd,1\neven,1,>\nodd,_\ncarry,_,<\n\
+             carry,1\ncarry,0,<\ncarry,0\ndone,1,-\ncarry,_\ndone,1,-",
+        )
+        .unwrap();
+        let reference = tmsim::from_tmsim(INCREMENT).unwrap();
+        let report = Equivalence::new(alphabet(), 6)
+            .with_random_inputs(20, 40, 7)
+            .check(&reference, &machine)
+            .unwrap();
+        assert_eq!(report.difference, None);
+        assert_eq!(report.inputs, 127 + 20);
+        assert!(report.inconclusive.is_empty());
+    }
+
+    #[test]
+    fn first_difference() {
+        let reference = tmsim::from_tmsim(INCREMENT).unwrap();
+        // forgets the carry
+        let machine = tmsim::from_tmsim(&INCREMENT.replace("carry,0,<", "done,0,-")).unwrap();
+        let report = Equivalence::new(alphabet(), 4)
+            .check(&reference, &machine)
+            .unwrap();
+        let difference = report.difference.unwrap();
+        assert_eq!(difference.input, vec!["1"]);
+        assert_eq!(difference.left.outcome, RunOutcome::Accepted);
+        assert_eq!(difference.left.band, vec!["1", "0"]);
+        assert_eq!(difference.right.band, vec!["0"]);
+
+        // never stops walking right
+        let machine = tmsim::from_tmsim(&INCREMENT.replace("carry,_,<", "right,_,>")).unwrap();
+        let report = Equivalence::new(alphabet(), 4)
+            .check(&reference, &machine)
+            .unwrap();
+        let difference = report.difference.unwrap();
+        assert!(difference.input.is_empty());
+        assert!(matches!(
+            difference.right.outcome,
+            RunOutcome::TranslatedCycle { .. }
+        ));
+    }
+
+    #[test]
+    fn inconclusive() {
+        let reference = tmsim::from_tmsim(INCREMENT).unwrap();
+        // counts up forever instead of stopping after the carry
+        let machine = tmsim::from_tmsim(&INCREMENT.replace("done,1,-", "right,1,>")).unwrap();
+        let report = Equivalence::new(alphabet(), 2)
+            .with_limits(Limits {
+                steps: Some(1000),
+                ..Limits::default()
+            })
+            .check(&reference, &machine)
+            .unwrap();
+        assert_eq!(report.difference, None);
+        assert_eq!(report.inconclusive.len(), 7);
+    }
+}
@@ -0,0 +1,325 @@
+use std::time::{Duration, Instant};
+
+use crate::ast::{TmBlock, TmDef, TmDir, TmOperation, TmStep, TmStmt};
+use crate::sim::{Limits, RunOutcome, Tape};
+use crate::tm::{Direction, TapeMode};
+
+/// The step of an empty block.
+const IDENTITY: TmOperation = TmOperation::Move {
+    replace: None,
+    direction: TmDir::Stay,
+};
+
+/// How a statement hands on control.
+enum Flow {
+    /// Go on with the next statement.
+    Next,
+    /// Leave the innermost cycle, or the program at the top level.
+    Break,
+    Halt,
+    Stop(RunOutcome),
+}
+
+/// Runs a program by walking its statements, without compiling it. Meant as
+/// the reference for `ast::into_machine`: the outcome, the number of steps
+/// and the band are the same as with the compiled machine.
+///
+/// What the statements mean:
+/// - a step writes and moves by the case of the symbol under the head, or by
+///   its default. Without either the program rejects. An empty block is a
+///   step that does nothing.
+/// - `break` leaves the innermost cycle, at the top level it leaves the
+///   program, which accepts. So does `halt`, from anywhere.
+/// - `cycle { .. }` runs its block again and again until something leaves it.
+/// - `branch c { .. }` is a conditional exit. If `c` has a case or a default
+///   that isn't `break`, that step is done, then the body, and then the branch
+///   leaves the innermost cycle, or the program at the top level. Otherwise
+///   reading the symbol is a step that does nothing and the program goes on
+///   after the branch. `test.tm` ends cycles with `branch _ -> 5 { }`.
+pub struct Interpreter<'a> {
+    def: &'a TmDef,
+    tape: Tape,
+    steps: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(def: &'a TmDef, input: Vec<String>) -> Self {
+        Self {
+            def,
+            tape: Tape::new(input, TapeMode::default()),
+            steps: 0,
+        }
+    }
+
+    /// Like `Machine::with_tape_mode`, for comparing with a compiled machine
+    /// that got the same mode.
+    pub fn with_tape_mode(mut self, tape_mode: TapeMode) -> Self {
+        self.tape = Tape::new(self.tape.symbols(), tape_mode);
+        self
+    }
+
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+
+    /// Number of executed steps.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Runs the program from the start. Leaving the program and `halt`
+    /// accept.
+    pub fn run(&mut self, limits: &Limits) -> RunOutcome {
+        let start = Instant::now();
+        match self.block(&self.def.block, limits, start) {
+            Flow::Next | Flow::Break | Flow::Halt => RunOutcome::Accepted,
+            Flow::Stop(outcome) => outcome,
+        }
+    }
+
+    fn block(&mut self, block: &TmBlock, limits: &Limits, start: Instant) -> Flow {
+        if block.0.is_empty() {
+            return self.step(Some(&IDENTITY), limits, start);
+        }
+        for statement in block.0.iter() {
+            match self.statement(statement, limits, start) {
+                Flow::Next => {}
+                flow => return flow,
+            }
+        }
+        Flow::Next
+    }
+
+    fn statement(&mut self, statement: &TmStmt, limits: &Limits, start: Instant) -> Flow {
+        match statement {
+            TmStmt::Step(step) => self.step(self.operation(step), limits, start),
+            TmStmt::Cycle(block) => loop {
+                match self.block(block, limits, start) {
+                    Flow::Next => {}
+                    Flow::Break => return Flow::Next,
+                    flow => return flow,
+                }
+            },
+            TmStmt::Branch { condition, body } => match self.operation(condition) {
+                None | Some(TmOperation::Break) => self.step(Some(&IDENTITY), limits, start),
+                operation => match self.step(operation, limits, start) {
+                    // a `break` in the body leaves the same cycle as its end
+                    Flow::Next => match self.block(body, limits, start) {
+                        Flow::Next | Flow::Break => Flow::Break,
+                        flow => flow,
+                    },
+                    flow => flow,
+                },
+            },
+        }
+    }
+
+    /// The operation of `step` on the symbol under the head.
+    fn operation<'s>(&self, step: &'s TmStep) -> Option<&'s TmOperation> {
+        step.cases.get(self.tape.read()).or(step.default.as_ref())
+    }
+
+    /// Executes a step with `operation`, `None` rejects.
+    fn step(&mut self, operation: Option<&TmOperation>, limits: &Limits, start: Instant) -> Flow {
+        if limits.steps.map_or(false, |max| self.steps >= max) {
+            return Flow::Stop(RunOutcome::StepLimitExceeded);
+        }
+        if limits
+            .milliseconds
+            .map_or(false, |max| start.elapsed() >= Duration::from_millis(max))
+        {
+            return Flow::Stop(RunOutcome::TimeLimitExceeded);
+        }
+        let (flow, replace, direction) = match operation {
+            Some(TmOperation::Move { replace, direction }) => {
+                let direction = match direction {
+                    TmDir::Left => Direction::Left,
+                    TmDir::Right => Direction::Right,
+                    TmDir::Stay => Direction::Unchanged,
+                };
+                (Flow::Next, replace.clone(), direction)
+            }
+            Some(TmOperation::Break) => (Flow::Break, None, Direction::Unchanged),
+            Some(TmOperation::Halt) => (Flow::Halt, None, Direction::Unchanged),
+            None => {
+                return Flow::Stop(RunOutcome::Rejected {
+                    symbols: vec![self.tape.read().to_string()],
+                })
+            }
+        };
+
+        if self.tape.falls_off(&direction) {
+            return Flow::Stop(RunOutcome::FellOffTape { band: 0 });
+        }
+        if let Some(symbol) = replace {
+            self.tape.write(symbol);
+        }
+        self.tape.move_head(&direction);
+        self.steps += 1;
+        if limits.cells.map_or(false, |max| self.tape.len() > max) {
+            return Flow::Stop(RunOutcome::TapeLimitExceeded);
+        }
+        flow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use crate::ast::{self, AtomicTmStep};
+    use crate::parser;
+    use crate::random::Random;
+    use crate::sim::{MachineExecutor, Simulator};
+
+    const SYMBOLS: [&str; 3] = ["_", "a", "b"];
+
+    /// Only the symbols up to the last one that isn't blank, every symbol of
+    /// the programs is one character.
+    fn band(symbols: Vec<String>) -> String {
+        symbols.concat().trim_end_matches('_').to_string()
+    }
+
+    #[test]
+    fn programs() {
+        let replace = "fn replace [a, b, _] {
+            cycle { a -> b >> or break; }
+            _ -> a;
+        }";
+        let exit = "fn exit [a, b, _] {
+            cycle {
+                branch b >> { }
+                a -> b >>;
+            }
+            _ -> a;
+        }";
+        let clear = "fn clear [a, _] {
+            branch a -> _ { }
+            _ -> a >>;
+            halt;
+        }";
+        let nested = "fn nested [a, b, _] {
+            cycle {
+                branch a -> b >> or break {
+                    cycle { b >> or break; }
+                    break;
+                }
+                _ -> b <<;
+            }
+            _ -> a;
+        }";
+        let stop = "fn stop [a, _] {
+            cycle {
+                cycle { a >> or halt; }
+            }
+        }";
+        let rejected = RunOutcome::Rejected {
+            symbols: vec!["b".to_string()],
+        };
+
+        for (source, input, outcome, tape, steps) in [
+            // the break at the blank leaves the cycle
+            (replace, "aa", RunOutcome::Accepted, "bba", 4),
+            // the branch reads a twice without doing anything, at b it moves,
+            // runs its empty body and leaves the cycle
+            (exit, "aab", RunOutcome::Accepted, "bbba", 7),
+            // at the top level the branch leaves the program
+            (clear, "a", RunOutcome::Accepted, "", 2),
+            (clear, "", RunOutcome::Accepted, "a", 3),
+            // the inner break leaves the inner cycle, the break in the body
+            // the outer one
+            (nested, "a", RunOutcome::Accepted, "ba", 4),
+            // `or break` skips the body, then the step has no case for b
+            (nested, "b", rejected, "b", 1),
+            (stop, "aa", RunOutcome::Accepted, "aa", 3),
+        ] {
+            let def = parser::parse_def(source).unwrap();
+            let input = input.chars().map(String::from).collect::<Vec<_>>();
+            let mut interpreter = Interpreter::new(&def, input.clone());
+            assert_eq!(interpreter.run(&Limits::default()), outcome);
+            assert_eq!(band(interpreter.tape().symbols()), tape);
+            assert_eq!(interpreter.steps(), steps);
+
+            let (machine, _) = ast::into_machine(def.clone()).unwrap();
+            let mut executor = MachineExecutor::new(machine, input);
+            assert_eq!(executor.run(&Limits::default()), outcome);
+            assert_eq!(band(executor.band_symbols().remove(0)), tape);
+            assert_eq!(executor.steps(), steps);
+        }
+    }
+
+    fn operation(random: &mut Random) -> TmOperation {
+        match random.below(8) {
+            0 => TmOperation::Break,
+            1 => TmOperation::Halt,
+            _ => TmOperation::Move {
+                replace: [None, Some("a"), Some("b")][random.below(3)].map(String::from),
+                direction: [TmDir::Left, TmDir::Right, TmDir::Stay][random.below(3)],
+            },
+        }
+    }
+
+    fn step(random: &mut Random) -> TmStep {
+        let mut atomic_steps = Vec::new();
+        for _ in 0..random.below(3) {
+            let patterns = SYMBOLS
+                .iter()
+                .filter(|_| random.below(2) == 0)
+                .map(|s| s.to_string())
+                .collect();
+            let (replace, direction) = match operation(random) {
+                TmOperation::Move { replace, direction } => (replace, direction),
+                _ => (None, TmDir::Right),
+            };
+            atomic_steps.push(AtomicTmStep::new(patterns, replace, direction));
+        }
+        let default = (random.below(2) == 0).then(|| operation(random));
+        TmStep::new(atomic_steps, default)
+    }
+
+    fn block(random: &mut Random, depth: usize) -> TmBlock {
+        let statements = (0..random.below(4))
+            .map(|_| match random.below(if depth == 0 { 1 } else { 4 }) {
+                0 | 1 => TmStmt::Step(step(random)),
+                2 => TmStmt::Branch {
+                    condition: step(random),
+                    body: block(random, depth - 1),
+                },
+                _ => TmStmt::Cycle(block(random, depth - 1)),
+            })
+            .collect();
+        TmBlock::new(statements)
+    }
+
+    #[test]
+    fn matches_compiled_machine() {
+        let mut random = Random::new(0x2545_f491_4f6c_dd1d);
+        let alphabet = SYMBOLS
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<HashSet<_>>();
+        let limits = Limits {
+            steps: Some(200),
+            ..Limits::default()
+        };
+        for _ in 0..500 {
+            let def = TmDef::new(
+                "random".to_string(),
+                alphabet.clone(),
+                block(&mut random, 3),
+            );
+            let (machine, _) = ast::into_machine(def.clone()).unwrap();
+            for _ in 0..10 {
+                let input = (0..random.below(6))
+                    .map(|_| SYMBOLS[1 + random.below(2)].to_string())
+                    .collect::<Vec<_>>();
+                let mut interpreter = Interpreter::new(&def, input.clone());
+                let mut executor = MachineExecutor::new(machine.clone(), input);
+                assert_eq!(interpreter.run(&limits), executor.run(&limits));
+                assert_eq!(interpreter.steps(), executor.steps());
+                assert_eq!(interpreter.tape(), &executor.bands()[0]);
+            }
+        }
+    }
+}
@@ -1 +1,19 @@
+pub mod ast;
+pub mod beaver;
+pub mod binary;
+pub mod compact;
+pub mod coverage;
+pub mod dot;
+pub mod equivalence;
+pub mod interpreter;
+pub mod parser;
+pub mod random;
+pub mod sim;
+pub mod single_tape;
+pub mod spacetime;
+#[cfg(test)]
+mod test_util;
+pub mod tikz;
 pub mod tm;
+pub mod tmsim;
+pub mod utm;
@@ -1,10 +1,249 @@
-mod ast;
-mod parser;
-mod tm;
+use std::env;
+use std::fs;
+use std::io::{stdin, BufReader, ErrorKind, LineWriter, Read, Write};
+use std::process;
 
-use std::io::{stdin, Read};
+use compiler::beaver::{self, Enumeration};
+use compiler::coverage::Coverage;
+use compiler::equivalence::Equivalence;
+use compiler::sim::trace;
+use compiler::sim::{Limits, MachineExecutor};
+use compiler::{ast, parser, tmsim};
+
+const USAGE: &str = "usage: compiler run <machine.tmsim> [input]
+       compiler beaver <states> <symbols> <steps> <results.jsonl>
+       compiler equivalent <left.tmsim> <right.tmsim> <length> [symbols]
+       compiler coverage <program.tm> <inputs...>
+       compiler trace diff <left.jsonl> <right.jsonl>";
+
+/**
+ * compiler run <machine.tmsim> [input]
+ *
+ * runs a machine written in the turingmachinesimulator.com syntax, every
+ * character of the input is a symbol. prints why the run stopped, which
+ * can be a proof that it never halts, and the statistics of the run.
+ */
+fn run(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let src = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let machine = tmsim::from_tmsim(&src).map_err(|e| e.to_string())?;
+    let input = args
+        .get(1)
+        .map(|input| input.chars().map(String::from).collect())
+        .unwrap_or_default();
+
+    let mut executor = MachineExecutor::new(machine, input);
+    executor.collect_statistics();
+    let limits = Limits {
+        steps: Some(10_000_000),
+        ..Limits::default()
+    };
+    let outcome = executor.run_detecting_cycles(&limits, |_| {});
+    println!("outcome: {:?}", outcome);
+    print!("{}", executor.statistics().unwrap());
+    Ok(())
+}
+
+/**
+ * compiler beaver <states> <symbols> <steps> <results.jsonl>
+ *
+ * enumerates the machines in tree normal form and appends a line to the
+ * results file for every machine. machines already in the file are skipped,
+ * so an interrupted enumeration continues where it stopped. the first line
+ * of the file holds the arguments, a file written with other ones is
+ * refused. prints a summary with the busy beaver candidate.
+ */
+fn beaver(args: &[String]) -> Result<(), String> {
+    let (states, symbols, steps, path) = match args {
+        [states, symbols, steps, path] => (states, symbols, steps, path),
+        _ => return Err(USAGE.to_string()),
+    };
+    let number = |arg: &String| arg.parse::<usize>().map_err(|e| e.to_string());
+    let enumeration = Enumeration::new(number(states)?, number(symbols)?, number(steps)?)?
+        .with_default_deciders();
+
+    // a line cut off by an interruption is dropped and written again
+    let mut written = fs::read(path).or_else(|e| match e.kind() {
+        ErrorKind::NotFound => Ok(Vec::new()),
+        _ => Err(e.to_string()),
+    })?;
+    let complete = written
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+    written.truncate(complete);
+    let (parameters, done) =
+        beaver::read_results(&written[..]).map_err(|e| format!("{}: {}", path, e))?;
+    if let Some(parameters) = parameters.filter(|p| *p != enumeration.parameters()) {
+        return Err(format!("{}: the results are for {}", path, parameters));
+    }
+    let done = done
+        .into_iter()
+        .map(|classified| (classified.machine, classified.verdict))
+        .collect();
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    file.set_len(written.len() as u64)
+        .map_err(|e| e.to_string())?;
+    let mut file = LineWriter::new(file);
+    if parameters.is_none() {
+        serde_json::to_writer(&mut file, &enumeration.parameters()).map_err(|e| e.to_string())?;
+        writeln!(file).map_err(|e| e.to_string())?;
+    }
+    let summary = enumeration
+        .run(&done, |classified| {
+            serde_json::to_writer(&mut file, classified)?;
+            writeln!(file)
+        })
+        .map_err(|e| e.to_string())?;
+    print!("{}", summary);
+    Ok(())
+}
+
+/**
+ * compiler equivalent <left.tmsim> <right.tmsim> <length> [symbols]
+ *
+ * runs both machines on every input up to the length and on random longer
+ * ones, symbols is a comma separated list of input symbols. without symbols
+ * the inputs use every symbol but the blank that both alphabets have. prints
+ * the first input the machines don't agree on and fails if there is one, or
+ * if a run hit the step limit and an input couldn't be compared.
+ */
+fn equivalent(args: &[String]) -> Result<(), String> {
+    let (left, right, length) = match args {
+        [left, right, length] | [left, right, length, _] => (left, right, length),
+        _ => return Err(USAGE.to_string()),
+    };
+    let load = |path: &String| {
+        let src = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        tmsim::from_tmsim(&src).map_err(|e| format!("{}: {}", path, e))
+    };
+    let (left, right) = (load(left)?, load(right)?);
+    let length = length.parse::<usize>().map_err(|e| e.to_string())?;
+    let alphabet = match args.get(3) {
+        Some(symbols) => symbols.split(',').map(String::from).collect(),
+        None => {
+            let mut alphabet = left
+                .alphabet
+                .intersection(&right.alphabet)
+                .filter(|s| *s != "_")
+                .cloned()
+                .collect::<Vec<_>>();
+            alphabet.sort();
+            alphabet
+        }
+    };
+
+    let report = Equivalence::new(alphabet, length)
+        .with_random_inputs(1000, 4 * length.max(1), 1)
+        .check(&left, &right)?;
+    print!("{}", report);
+    if report.difference.is_some() {
+        return Err("the machines are not equivalent".to_string());
+    }
+    if !report.inconclusive.is_empty() {
+        for input in report.inconclusive.iter() {
+            eprintln!("inconclusive: {}", input.join(" "));
+        }
+        return Err(format!(
+            "{} inputs couldn't be compared within the step limit",
+            report.inconclusive.len()
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * compiler coverage <program.tm> <inputs...>
+ *
+ * compiles the program and runs it on every input, the symbols of an input
+ * are separated by commas. prints the lines and alternatives that ran as an
+ * lcov tracefile, the outcome of every run goes to stderr.
+ */
+fn coverage(args: &[String]) -> Result<(), String> {
+    let (path, inputs) = args.split_first().ok_or(USAGE)?;
+    let src = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let def = parser::parse_def(&src).map_err(|e| format!("{}: {}", path, e))?;
+    let name = def.identifier().to_string();
+    let (machine, source_map) = ast::into_machine(def)?;
+
+    let mut coverage = Coverage::new(machine, source_map);
+    let limits = Limits {
+        steps: Some(10_000_000),
+        ..Limits::default()
+    };
+    for input in inputs {
+        let symbols = input
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+        let outcome = coverage.record(symbols, &limits);
+        eprintln!("{}: {:?}", input, outcome);
+    }
+    print!("{}", coverage.to_lcov(&name, path));
+    Ok(())
+}
+
+/**
+ * compiler trace diff <left.jsonl> <right.jsonl>
+ *
+ * compares two trace files step by step, the line numbers don't have to
+ * match. prints the first step they don't agree on with the line and the
+ * entry of both sides and fails, or that the traces are the same.
+ */
+fn trace(args: &[String]) -> Result<(), String> {
+    let (left, right) = match args {
+        [command, left, right] if command == "diff" => (left, right),
+        _ => return Err(USAGE.to_string()),
+    };
+    let load = |path: &String| {
+        let file = fs::File::open(path).map_err(|e| e.to_string())?;
+        trace::read_trace(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))
+    };
+    let divergence = match trace::first_divergence(&load(left)?, &load(right)?) {
+        Some(divergence) => divergence,
+        None => {
+            println!("the traces are the same");
+            return Ok(());
+        }
+    };
+
+    println!("first divergence at step {}", divergence.step);
+    for (path, side) in [(left, &divergence.left), (right, &divergence.right)] {
+        match side {
+            Some((line, entry)) => {
+                let entry = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+                println!("{}:{}: {}", path, line, entry);
+            }
+            None => println!("{}: ended", path),
+        }
+    }
+    Err("the traces differ".to_string())
+}
 
 fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.first().map(String::as_str) {
+        Some("run") => Some(run(&args[1..])),
+        Some("beaver") => Some(beaver(&args[1..])),
+        Some("equivalent") => Some(equivalent(&args[1..])),
+        Some("coverage") => Some(coverage(&args[1..])),
+        Some("trace") => Some(trace(&args[1..])),
+        _ => None,
+    };
+    if let Some(result) = result {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     let mut contents = String::new();
     stdin().read_to_string(&mut contents);
 
@@ -1,136 +1,237 @@
-
-use nom::sequence::delimited;
-use nom::multi::many0;
-use nom::multi::separated_list0;
-use nom::combinator::recognize;
-use nom::sequence::preceded;
-use nom::bytes::complete::tag;
-use nom::combinator::opt;
-use nom::sequence::tuple;
-use nom::character::complete::space0;
-use nom::sequence::terminated;
-use nom::error::ParseError;
-use nom::Err;
-use nom::bytes::complete::is_a;
 use nom::branch::alt;
-use nom::character::complete::char;
-use nom::combinator::map_opt;
-use nom::character::is_alphanumeric;
-use nom::bytes::complete::take_while;
-use nom::character::complete::alpha1;
-use nom::character::complete::alphanumeric0;
-use nom::sequence::pair;
-use nom::IResult;
-use nom::Parser;
-use nom::combinator::map;
-use nom::error::{Error, ErrorKind};
-
-use either::Either;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{char, multispace0, satisfy};
+use nom::combinator::{eof, not, opt, recognize, verify};
+use nom::multi::{many0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use nom::{IResult, Parser};
+
 use once_cell::sync::Lazy;
 use std::collections::HashSet;
+use std::fmt;
 
 use super::ast;
 
-struct TmParser(HashSet<String>);
-
 static KEYWORDS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
-    vec!["cycle", "branch", "break", "halt"].into_iter().collect()
+    vec!["fn", "cycle", "branch", "or", "break", "halt"]
+        .into_iter()
+        .collect()
 });
 
-enum IdentError<I> {
-    UnexpectedCharacter(nom::error::Error<I>),
-    UnexpectedKeyword,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
 }
 
-impl TmParser {
-    fn parse_def(input: &str) -> IResult<&str, ast::TmDef> {
-        todo!()
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
     }
+}
 
-    fn tm_ident(input: &str) -> IResult<&str, String, IdentError<&str>> {
-        let ident = || {
-            recognize(alt((alpha1, is_a("_")))
-                .and(take_while(|c| is_alphanumeric(c as u8) || c == '_')))
-        };
+impl std::error::Error for ParseError {}
+
+/// Parses a program, every step gets the line it starts on.
+///
+/// ```text
+/// fn name [a, b, _] {
+///     branch a -> b >> { cycle { [a, b] >> or break; } }
+///     _ << or halt;
+/// }
+/// ```
+pub fn parse_def(source: &str) -> Result<ast::TmDef, ParseError> {
+    let parser = TmParser { source };
+    let result = terminated(|input| parser.tm_def(input), ws(eof)).parse(source);
+    match result {
+        Ok((_, def)) => Ok(def),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            let rest = e.input.trim_start();
+            let near = rest.lines().next().unwrap_or_default();
+            Err(ParseError {
+                line: parser.line(rest),
+                message: match near {
+                    "" => "unexpected end of input".to_string(),
+                    near => format!("unexpected `{}`", near.trim_end()),
+                },
+            })
+        }
+        Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers only"),
+    }
+}
+
+/// Skips the whitespace in front of `parser`.
+fn ws<'a, O>(
+    parser: impl Parser<&'a str, O, nom::error::Error<&'a str>>,
+) -> impl Parser<&'a str, O, nom::error::Error<&'a str>> {
+    preceded(multispace0, parser)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_alphanumeric() || "_'\"#$!".contains(c)
+}
+
+/// A keyword that isn't the start of a longer word.
+fn keyword<'a>(word: &'static str) -> impl Parser<&'a str, &'a str, nom::error::Error<&'a str>> {
+    ws(terminated(tag(word), not(satisfy(is_symbol_char))))
+}
+
+struct TmParser<'s> {
+    source: &'s str,
+}
+
+impl<'s> TmParser<'s> {
+    /// Line of the start of `rest`, which has to be a suffix of the source.
+    fn line(&self, rest: &str) -> usize {
+        let offset = self.source.len() - rest.len();
+        self.source[..offset].matches('\n').count() + 1
+    }
+
+    fn tm_def(&self, input: &'s str) -> IResult<&'s str, ast::TmDef> {
+        let alphabet = delimited(
+            ws(char('[')),
+            separated_list1(ws(char(',')), Self::tm_alpha),
+            ws(char(']')),
+        );
+
+        tuple((keyword("fn"), Self::tm_ident, alphabet, |input| {
+            self.tm_block(input)
+        }))
+        .map(|(_, name, alphabet, block)| {
+            ast::TmDef::new(name, alphabet.into_iter().collect(), block)
+        })
+        .parse(input)
+    }
+
+    fn tm_ident(input: &str) -> IResult<&str, String> {
+        let ident = recognize(pair(
+            satisfy(|c| c.is_alphabetic() || c == '_'),
+            take_while(|c: char| c.is_alphanumeric() || c == '_'),
+        ));
 
-        ident()
+        ws(verify(ident, |res: &str| !KEYWORDS.contains(res)))
+            .map(String::from)
             .parse(input)
-            .map_err(|e| e.map(IdentError::UnexpectedCharacter))
-            .and_then(|(xs, res)| {
-                if KEYWORDS.contains(res) {
-                    Err(Err::Error(IdentError::UnexpectedKeyword))
-                }
-                else {
-                    Ok((xs, String::from(res)))
-                }
-            })
     }
 
-    fn tm_alpha<'a>(&self, input: &'a str) -> IResult<&'a str, String> {
-        let alpha = || {
-            take_while(|c| is_alphanumeric(c as u8) 
-                || c == '_' || c == '\'' || c == '\"'
-                || c == '#' || c == '$' || c == '!'
-            )
-        };
+    fn tm_alpha(input: &str) -> IResult<&str, String> {
+        ws(verify(take_while1(is_symbol_char), |res: &str| {
+            !KEYWORDS.contains(res)
+        }))
+        .map(String::from)
+        .parse(input)
+    }
 
-        map_opt(alpha(), |res| {
-            if KEYWORDS.contains(res) || self.0.contains(res) {
-                None
-            }
-            else {
-                Some(String::from(res))
-            }
-        })(input)
+    fn tm_block(&self, input: &'s str) -> IResult<&'s str, ast::TmBlock> {
+        delimited(
+            ws(char('{')),
+            many0(|input| self.tm_stmt(input)),
+            ws(char('}')),
+        )
+        .map(ast::TmBlock::new)
+        .parse(input)
     }
 
-    fn tm_block<'a>(&self, input: &'a str) -> IResult<&'a str, ast::TmBlock> {
-        let alpha_closure = |||input| self.tm_alpha(input);
-
-        let simple_step = || {
-            tuple((
-                terminated(alpha_closure(), space0).map(|x| vec![x]),
-                opt(preceded(tuple((tag("->"), space0)), alpha_closure())),
-                opt(terminated(alt((
-                    tag(">>").map(|_| ast::TmDir::Right), 
-                    tag("<<").map(|_| ast::TmDir::Left))), 
-                    space0))
-                    .map(|dir| dir.unwrap_or(ast::TmDir::Stay))
-            ))
-                .map(|(lhs, rhs, dir)| ast::AtomicTmStep::new(lhs, rhs, dir))
+    fn tm_stmt(&self, input: &'s str) -> IResult<&'s str, ast::TmStmt> {
+        let block = |input| self.tm_block(input);
+        let step = |input| self.tm_step(input);
+
+        alt((
+            preceded(keyword("cycle"), block).map(ast::TmStmt::Cycle),
+            preceded(keyword("branch"), pair(step, block))
+                .map(|(condition, body)| ast::TmStmt::Branch { condition, body }),
+            terminated(step, ws(char(';'))).map(ast::TmStmt::Step),
+        ))
+        .parse(input)
+    }
+
+    fn tm_step(&self, input: &'s str) -> IResult<&'s str, ast::TmStep> {
+        let (input, _) = multispace0(input)?;
+        let line = self.line(input);
+
+        let direction = || {
+            opt(ws(alt((
+                tag(">>").map(|_| ast::TmDir::Right),
+                tag("<<").map(|_| ast::TmDir::Left),
+            ))))
+            .map(|dir| dir.unwrap_or(ast::TmDir::Stay))
         };
 
-        let step_sep = || terminated(tag("or"), space0);
+        let patterns = alt((
+            Self::tm_alpha.map(|x| vec![x]),
+            delimited(
+                ws(char('[')),
+                separated_list1(ws(char(',')), Self::tm_alpha),
+                ws(char(']')),
+            ),
+        ));
+
+        let simple_step = tuple((
+            patterns,
+            opt(preceded(ws(tag("->")), Self::tm_alpha)),
+            direction(),
+        ))
+        .map(|(lhs, rhs, dir)| ast::AtomicTmStep::new(lhs, rhs, dir));
 
         let default_step = || {
-            terminated(alt((
-                tag("break").map(|_| ast::TmOperation::Break),
-                tag("halt").map(|_| ast::TmOperation::Halt),
-                tag("<<").map(|_| ast::TmOperation::Move { 
-                    replace: None, 
-                    direction: ast::TmDir::Left
+            alt((
+                keyword("break").map(|_| ast::TmOperation::Break),
+                keyword("halt").map(|_| ast::TmOperation::Halt),
+                ws(tag("<<")).map(|_| ast::TmOperation::Move {
+                    replace: None,
+                    direction: ast::TmDir::Left,
                 }),
-                tag(">>").map(|_| ast::TmOperation::Move {
+                ws(tag(">>")).map(|_| ast::TmOperation::Move {
                     replace: None,
-                    direction: ast::TmDir::Right
-                })
-            )), space0)
-        };
-
-        let step = || {
-            tuple((
-                separated_list0(step_sep(), simple_step()),
-                opt(preceded(step_sep(), default_step()))
+                    direction: ast::TmDir::Right,
+                }),
             ))
-                .map(|(act, def)| ast::TmStep::new(act, def))
         };
 
-        let stmt = || {
-            terminated(step().map(ast::TmStmt::Step), char(';'))
-        };
+        alt((
+            pair(
+                separated_list1(keyword("or"), simple_step),
+                opt(preceded(keyword("or"), default_step())),
+            )
+            .map(|(act, def)| ast::TmStep::new(act, def)),
+            default_step().map(|def| ast::TmStep::new(vec![], Some(def))),
+        ))
+        .map(|step| step.at_line(line))
+        .parse(input)
+    }
+}
 
-        delimited(terminated(char('{'), space0), many0(stmt()), char('}'))
-            .map(ast::TmBlock::new)
-            .parse(input)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiply() {
+        let def = parse_def(include_str!("../test.tm")).unwrap();
+        assert_eq!(def.identifier(), "multiply");
+        let (_, source_map) = ast::into_machine(def).unwrap();
+        let mut lines = source_map
+            .statements
+            .values()
+            .map(|source| source.line)
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                2, 3, 6, 7, 8, 10, 11, 12, 15, 16, 19, 20, 23, 25, 27, 28, 30, 31, 32, 33, 36, 39,
+                40, 42, 44, 45, 46
+            ]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn errors() {
+        let error = parse_def("fn f [a] {\n    a >>;\n    a -> ;\n}")
+            .err()
+            .unwrap();
+        assert_eq!(error.line, 3);
+        assert!(parse_def("fn f [a] {\n    cycle { a >>; }\n").is_err());
+        assert!(parse_def("fn cycle [a] { }").is_err());
+    }
+}
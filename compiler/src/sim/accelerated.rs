@@ -0,0 +1,292 @@
+use std::iter;
+use std::time::{Duration, Instant};
+
+use super::dense::{TransitionTable, KEEP};
+use super::{FellOffTape, Limits, RunOutcome, SimulationStep, Simulator, TIME_CHECK_INTERVAL};
+use crate::tm::{Direction, Machine, TapeMode};
+
+/// Runs of equal symbols on one side of the head, the run next to the head
+/// last. Blanks past the last run aren't stored.
+type Runs = Vec<(u16, usize)>;
+
+fn push(runs: &mut Runs, symbol: u16, count: usize) {
+    match runs.last_mut() {
+        Some(run) if run.0 == symbol => run.1 += count,
+        None if symbol == 0 => {}
+        _ => runs.push((symbol, count)),
+    }
+}
+
+/// Removes `count` cells from the run next to the head, which has to be
+/// long enough.
+fn take(runs: &mut Runs, count: usize) {
+    if count == 0 {
+        return;
+    }
+    let run = runs.last_mut().unwrap();
+    run.1 -= count;
+    if run.1 == 0 {
+        runs.pop();
+    }
+}
+
+/// Removes the cell next to the head and returns its symbol.
+fn take_one(runs: &mut Runs) -> u16 {
+    match runs.last() {
+        Some(&(symbol, _)) => {
+            take(runs, 1);
+            symbol
+        }
+        None => 0,
+    }
+}
+
+/// Runs a one band machine on a run length encoded band. When a state loops
+/// to itself while moving over a run of the symbol it reads, the whole run is
+/// passed in one macro step, so sweeps cost the same no matter how long the
+/// run is. Final configurations and step counts are the same as with
+/// `MachineExecutor`.
+pub struct AcceleratedExecutor {
+    table: TransitionTable,
+    left: Runs,
+    head: u16,
+    right: Runs,
+    position: isize,
+    /// Leftmost and rightmost cell `Tape` would store.
+    min: isize,
+    max: isize,
+    state: u32,
+    steps: usize,
+}
+
+impl AcceleratedExecutor {
+    pub fn new(machine: &Machine, input: Vec<String>) -> Result<Self, &'static str> {
+        if machine.size != 1 {
+            return Err("accelerated simulation needs a machine with one band");
+        }
+        if machine.tape_mode != TapeMode::TwoWayInfinite {
+            return Err("accelerated simulation needs a two way infinite band");
+        }
+        let table = TransitionTable::new(machine)?;
+        let input = table.input(&input).ok_or("input symbol not in alphabet")?;
+
+        let mut right = Runs::new();
+        for symbol in input.iter().skip(1).rev() {
+            push(&mut right, *symbol, 1);
+        }
+        Ok(Self {
+            state: table.state_index(&machine.start_state_name),
+            table,
+            left: Runs::new(),
+            head: input.first().copied().unwrap_or(0),
+            right,
+            position: 0,
+            min: 0,
+            max: input.len().max(1) as isize - 1,
+            steps: 0,
+        })
+    }
+
+    pub fn current_state_name(&self) -> &str {
+        &self.table.states[self.state as usize]
+    }
+
+    /// Number of executed steps, a macro step counts every step it stands
+    /// for.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Executes at most `max_steps` steps at once, stopping on the step that
+    /// adds the `max_new_cells`th cell. Returns the index of the transition
+    /// in the table and the number of steps.
+    fn step(&mut self, max_steps: usize, max_new_cells: usize) -> Option<(usize, usize)> {
+        let index = self.table.lookup(self.state, iter::once(self.head))?;
+        let transition = &self.table.transitions[index];
+        let write = match transition.writes[0] {
+            KEEP => self.head,
+            write => write,
+        };
+        let direction = &transition.moves[0];
+
+        let mut count = 1;
+        if transition.next == self.state {
+            let (ahead, stored) = match direction {
+                Direction::Right => (self.right.last(), self.max - self.position),
+                Direction::Left => (self.left.last(), self.position - self.min),
+                Direction::Unchanged => (None, 0),
+            };
+            if let Some(&(_, length)) = ahead.filter(|run| run.0 == self.head) {
+                count += length;
+            }
+            count = count
+                .min(max_steps)
+                .min((stored as usize).saturating_add(max_new_cells));
+        }
+
+        match direction {
+            Direction::Right => {
+                take(&mut self.right, count - 1);
+                push(&mut self.left, write, count);
+                self.head = take_one(&mut self.right);
+                self.position += count as isize;
+                self.max = self.max.max(self.position);
+            }
+            Direction::Left => {
+                take(&mut self.left, count - 1);
+                push(&mut self.right, write, count);
+                self.head = take_one(&mut self.left);
+                self.position -= count as isize;
+                self.min = self.min.min(self.position);
+            }
+            Direction::Unchanged => self.head = write,
+        }
+        self.state = transition.next;
+        self.steps += count;
+        Some((index, count))
+    }
+
+    /// Like `Simulator::run` with macro steps. The outcome and the
+    /// configuration it stops in are the same as with single steps, the time
+    /// limit is checked every `TIME_CHECK_INTERVAL` macro steps.
+    pub fn execute(&mut self, limits: &Limits) -> RunOutcome {
+        let start = Instant::now();
+        let mut steps = 0;
+        let mut macro_steps = 0usize;
+        loop {
+            if self.is_end_state() {
+                return RunOutcome::Accepted;
+            }
+            if limits.steps.map_or(false, |max| steps >= max) {
+                return RunOutcome::StepLimitExceeded;
+            }
+            if macro_steps % TIME_CHECK_INTERVAL == 0
+                && limits
+                    .milliseconds
+                    .map_or(false, |max| start.elapsed() >= Duration::from_millis(max))
+            {
+                return RunOutcome::TimeLimitExceeded;
+            }
+            let max_steps = limits.steps.map_or(usize::MAX, |max| max - steps);
+            let max_new_cells = limits
+                .cells
+                .map_or(usize::MAX, |max| max.saturating_sub(self.cells()) + 1);
+            match self.step(max_steps, max_new_cells) {
+                Some((_, count)) => steps += count,
+                None => {
+                    return RunOutcome::Rejected {
+                        symbols: self.read(),
+                    }
+                }
+            }
+            macro_steps += 1;
+            if limits.cells.map_or(false, |max| self.cells() > max) {
+                return RunOutcome::TapeLimitExceeded;
+            }
+        }
+    }
+}
+
+impl Simulator for AcceleratedExecutor {
+    fn next_step(&mut self) -> Result<Option<SimulationStep>, FellOffTape> {
+        let (state, read) = (self.state, self.read());
+        Ok(self
+            .step(1, usize::MAX)
+            .map(|(index, _)| self.table.simulation_step(state, index, read)))
+    }
+
+    fn is_end_state(&self) -> bool {
+        self.table.end_states[self.state as usize]
+    }
+
+    fn band_symbols(&self) -> Vec<Vec<String>> {
+        // the cells next to the head first, up to the last stored cell
+        let expand = |runs: &Runs, cells: isize| {
+            let mut symbols = runs
+                .iter()
+                .rev()
+                .flat_map(|&(symbol, count)| iter::repeat(symbol).take(count))
+                .collect::<Vec<_>>();
+            symbols.resize(cells as usize, 0);
+            symbols
+        };
+        let mut band = expand(&self.left, self.position - self.min);
+        band.reverse();
+        band.push(self.head);
+        band.extend(expand(&self.right, self.max - self.position));
+        vec![band
+            .into_iter()
+            .map(|symbol| self.table.symbols[symbol as usize].clone())
+            .collect()]
+    }
+
+    fn heads(&self) -> Vec<isize> {
+        vec![self.position]
+    }
+
+    fn read(&self) -> Vec<String> {
+        vec![self.table.symbols[self.head as usize].clone()]
+    }
+
+    fn cells(&self) -> usize {
+        (self.max - self.min + 1) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compact;
+    use crate::sim::tests::counter;
+    use crate::sim::MachineExecutor;
+
+    #[test]
+    fn accelerated_executor_matches() {
+        let machines = [
+            compact::from_compact("1RB1LB_1LA0LC_1RZ1LD_1RD0RA").unwrap(),
+            compact::from_compact("1RB1LC_1RC1RB_1RD0LE_1LA1LD_1RZ0LA").unwrap(),
+            compact::from_compact("1RB0LC_0LA1RC_1LA0RB").unwrap(),
+            counter(),
+        ];
+        let mut limits = (0..40)
+            .chain([100, 1000, 5000])
+            .map(|steps| Limits {
+                steps: Some(steps),
+                ..Limits::default()
+            })
+            .collect::<Vec<_>>();
+        limits.extend([1, 2, 10, 40].map(|cells| Limits {
+            steps: Some(5000),
+            cells: Some(cells),
+            ..Limits::default()
+        }));
+
+        for machine in machines.iter() {
+            for limits in limits.iter() {
+                let mut executor = MachineExecutor::new(machine.clone(), vec![]);
+                let mut accelerated = AcceleratedExecutor::new(machine, vec![]).unwrap();
+                assert_eq!(accelerated.execute(limits), executor.run(limits));
+                assert_eq!(accelerated.steps(), executor.steps());
+                assert_eq!(
+                    accelerated.current_state_name(),
+                    executor.current_state_name()
+                );
+                assert_eq!(accelerated.band_symbols(), executor.band_symbols());
+                assert_eq!(accelerated.heads(), executor.heads());
+            }
+        }
+
+        // the 5 state champion halts after 47,176,870 steps on 12,289 cells
+        let mut accelerated = AcceleratedExecutor::new(&machines[1], vec![]).unwrap();
+        assert_eq!(
+            accelerated.execute(&Limits::default()),
+            RunOutcome::Accepted
+        );
+        assert_eq!(accelerated.steps(), 47_176_870);
+        let ones = accelerated.band_symbols()[0]
+            .iter()
+            .filter(|symbol| *symbol == "1")
+            .count();
+        assert_eq!(ones, 4098);
+    }
+}
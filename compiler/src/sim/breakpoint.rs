@@ -0,0 +1,75 @@
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Limits, MachineExecutor, RunOutcome, SimulationStep, Simulator};
+
+/// A condition on the configuration, checked after every step.
+#[derive(Clone)]
+pub struct Predicate(pub Arc<dyn Fn(&MachineExecutor) -> bool + Send + Sync>);
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Predicate")
+    }
+}
+
+/// Stops a run after a step that matches. Predicates can't be serialized, so
+/// the frontend can only send the other kinds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Breakpoint {
+    /// The step entered the state from a different one, steps that stay in
+    /// the state don't hit.
+    State(String),
+    /// The step executed the transition function at `index` of `state`.
+    Transition { state: String, index: usize },
+    /// The step read `symbol` on `band`.
+    Read { band: usize, symbol: String },
+    /// The step wrote `symbol` on `band`.
+    Write { band: usize, symbol: String },
+    /// The head of `band` moved to `position`.
+    Head { band: usize, position: isize },
+    #[serde(skip)]
+    Predicate(Predicate),
+}
+
+impl Breakpoint {
+    pub fn is_hit(&self, executor: &MachineExecutor, step: &SimulationStep) -> bool {
+        match self {
+            Breakpoint::State(state) => {
+                step.state() != state && executor.current_state_name() == state
+            }
+            Breakpoint::Transition { state, index } => {
+                step.state() == state && step.transition_index() == *index
+            }
+            Breakpoint::Read { band, symbol } => step.read().get(*band) == Some(symbol),
+            Breakpoint::Write { band, symbol } => {
+                step.actions().get(*band).map(|action| &action.0) == Some(symbol)
+            }
+            Breakpoint::Head { band, position } => {
+                executor.bands().get(*band).map(|band| band.head()) == Some(*position)
+            }
+            Breakpoint::Predicate(predicate) => (predicate.0)(executor),
+        }
+    }
+}
+
+impl MachineExecutor {
+    /// Like `Simulator::run_with`, also stopping with `RunOutcome::Breakpoint`
+    /// after the first step that hits one of `breakpoints`.
+    pub fn run_until_breakpoint(
+        &mut self,
+        breakpoints: &[Breakpoint],
+        limits: &Limits,
+        mut on_step: impl FnMut(SimulationStep),
+    ) -> RunOutcome {
+        self.run_until(limits, |executor, step| {
+            let hit = breakpoints
+                .iter()
+                .position(|breakpoint| breakpoint.is_hit(executor, &step));
+            on_step(step);
+            hit.map(|index| RunOutcome::Breakpoint { index })
+        })
+    }
+}
@@ -0,0 +1,267 @@
+use super::{Limits, MachineExecutor, RunOutcome, SimulationStep, Simulator, Tape};
+use crate::tm::TapeMode;
+
+/// The first and one past the last cell of a band that aren't blank, `(0, 0)`
+/// for a blank band.
+fn extent(band: &Tape) -> (isize, isize) {
+    let end = band.offset() + band.len() as isize;
+    let first = (band.offset()..end).find(|p| band.symbol_at(*p) != "_");
+    match first {
+        Some(first) => {
+            let last = (first..end).rev().find(|p| band.symbol_at(*p) != "_");
+            (first, last.unwrap() + 1)
+        }
+        None => (0, 0),
+    }
+}
+
+/// A configuration with the blanks at the ends of the bands trimmed, so it
+/// doesn't depend on how much of a band is stored.
+struct Configuration {
+    step: usize,
+    state: String,
+    heads: Vec<isize>,
+    /// The first cell that isn't blank and the cells up to the last one.
+    bands: Vec<(isize, Vec<String>)>,
+}
+
+impl Configuration {
+    fn new(executor: &MachineExecutor) -> Self {
+        let bands = executor
+            .bands()
+            .iter()
+            .map(|band| {
+                let (first, end) = extent(band);
+                let cells = (first..end).map(|p| band.symbol_at(p).to_string());
+                (first, cells.collect())
+            })
+            .collect();
+        Self {
+            step: executor.steps(),
+            state: executor.current_state_name().to_string(),
+            heads: executor.heads(),
+            bands,
+        }
+    }
+
+    /// Whether `executor` is in this configuration, comparing the cheap parts
+    /// first.
+    fn matches(&self, executor: &MachineExecutor) -> bool {
+        self.state == executor.current_state_name()
+            && self.heads == executor.heads()
+            && self
+                .bands
+                .iter()
+                .zip(executor.bands())
+                .all(|((first, cells), band)| {
+                    extent(band) == (*first, first + cells.len() as isize)
+                        && cells
+                            .iter()
+                            .enumerate()
+                            .all(|(i, symbol)| band.symbol_at(first + i as isize) == symbol)
+                })
+    }
+}
+
+/// The head reached a cell beyond every stored cell, so the band is blank
+/// from there on.
+struct Record {
+    step: usize,
+    state: String,
+    position: isize,
+    band: Tape,
+}
+
+/// Finds translated cycles from the records on one side of the band.
+struct Records {
+    /// 1 for records on the right, -1 for records on the left.
+    side: isize,
+    saved: Option<Record>,
+    /// How far the head went back from the side since the saved record.
+    back: isize,
+    count: usize,
+}
+
+impl Records {
+    fn new(side: isize) -> Self {
+        Self {
+            side,
+            saved: None,
+            back: 0,
+            count: 0,
+        }
+    }
+
+    /// Checks the record `executor` just made against the saved one. The
+    /// run from the saved record only read the cells up to `back`, so if
+    /// those are the same relative to the head now, it repeats from here
+    /// shifted along the band, forever.
+    fn record(&mut self, executor: &MachineExecutor) -> Option<RunOutcome> {
+        let band = &executor.bands()[0];
+        let position = band.head();
+        if let Some(saved) = self.saved.as_ref() {
+            if saved.state == executor.current_state_name() {
+                let depth = (saved.position - self.back) * self.side;
+                let shift = position - saved.position;
+                // on a bounded band the head may have bumped into cell 0,
+                // which it won't do shifted
+                let unbounded = band.mode() == TapeMode::TwoWayInfinite || self.back > 0;
+                let repeats = unbounded
+                    && (0..=depth).all(|i| {
+                        let p = saved.position - i * self.side;
+                        saved.band.symbol_at(p) == band.symbol_at(p + shift)
+                    });
+                if repeats {
+                    return Some(RunOutcome::TranslatedCycle {
+                        start: saved.step,
+                        period: executor.steps() - saved.step,
+                        shift,
+                    });
+                }
+            }
+        }
+        self.count += 1;
+        if self.count.is_power_of_two() {
+            self.saved = Some(Record {
+                step: executor.steps(),
+                state: executor.current_state_name().to_string(),
+                position,
+                band: band.clone(),
+            });
+            self.back = position;
+        }
+        None
+    }
+
+    fn track(&mut self, head: isize) {
+        self.back = match self.side {
+            1 => self.back.min(head),
+            _ => self.back.max(head),
+        };
+    }
+}
+
+/// Proves that a run never halts. The configuration is compared with one
+/// saved at steps that are powers of two, which finds every cycle once the
+/// saved configuration is part of it, without keeping every configuration.
+/// On machines with one band, records of the head are compared the same way
+/// to find configurations that repeat shifted along the band.
+pub struct CycleDetector {
+    saved: Configuration,
+    count: usize,
+    /// The stored cells of the one band before the step, `None` on machines
+    /// with more bands.
+    stored: Option<(isize, isize)>,
+    right: Records,
+    left: Records,
+}
+
+impl CycleDetector {
+    pub fn new(executor: &MachineExecutor) -> Self {
+        let band = &executor.bands()[0];
+        Self {
+            saved: Configuration::new(executor),
+            count: 0,
+            stored: (executor.bands().len() == 1)
+                .then(|| (band.offset(), band.offset() + band.len() as isize - 1)),
+            right: Records::new(1),
+            left: Records::new(-1),
+        }
+    }
+
+    /// Checks the configuration after a step, `Some` once the run is proven
+    /// to never halt.
+    pub fn check(&mut self, executor: &MachineExecutor) -> Option<RunOutcome> {
+        if self.saved.matches(executor) {
+            return Some(RunOutcome::Cycle {
+                start: self.saved.step,
+                period: executor.steps() - self.saved.step,
+            });
+        }
+        self.count += 1;
+        if self.count.is_power_of_two() {
+            self.saved = Configuration::new(executor);
+        }
+
+        let (first, last) = self.stored?;
+        let band = &executor.bands()[0];
+        let head = band.head();
+        self.right.track(head);
+        self.left.track(head);
+        self.stored = Some((band.offset(), band.offset() + band.len() as isize - 1));
+        if head > last {
+            self.right.record(executor)
+        } else if head < first {
+            self.left.record(executor)
+        } else {
+            None
+        }
+    }
+}
+
+impl MachineExecutor {
+    /// Like `Simulator::run_with`, also stopping with `RunOutcome::Cycle` or
+    /// `RunOutcome::TranslatedCycle` once a `CycleDetector` proves that the
+    /// run never halts.
+    pub fn run_detecting_cycles(
+        &mut self,
+        limits: &Limits,
+        mut on_step: impl FnMut(SimulationStep),
+    ) -> RunOutcome {
+        let mut detector = CycleDetector::new(self);
+        self.run_until(limits, |executor, step| {
+            on_step(step);
+            detector.check(executor)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compact;
+    use crate::sim::tests::counter;
+    use crate::tm::Machine;
+
+    #[test]
+    fn never_halts() {
+        let limits = Limits {
+            steps: Some(10_000),
+            ..Limits::default()
+        };
+        let outcome = |machine: Machine| {
+            MachineExecutor::new(machine, vec![]).run_detecting_cycles(&limits, |_| {})
+        };
+        let machine = |code| compact::from_compact(code).unwrap();
+
+        assert_eq!(
+            outcome(machine("0RB0LA_1LA0LA")),
+            RunOutcome::Cycle {
+                start: 4,
+                period: 4
+            }
+        );
+        assert_eq!(
+            outcome(machine("1RB0RB_1LB1RA")),
+            RunOutcome::TranslatedCycle {
+                start: 10,
+                period: 9,
+                shift: 3
+            }
+        );
+        assert_eq!(
+            outcome(machine("1LB0LB_1RB1LA")),
+            RunOutcome::TranslatedCycle {
+                start: 10,
+                period: 9,
+                shift: -3
+            }
+        );
+        // a counter never halts, but doesn't repeat either
+        assert_eq!(outcome(counter()), RunOutcome::StepLimitExceeded);
+        assert_eq!(
+            outcome(machine("1RB1LB_1LA0LC_1RZ1LD_1RD0RA")),
+            RunOutcome::Accepted
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::{FellOffTape, Limits, RunOutcome, SimulationStep, Simulator, TIME_CHECK_INTERVAL};
+use crate::tm::{Direction, Machine, TapeMode, WILDCARD};
+
+/// Largest number of entries of a transition table.
+const MAX_TABLE: usize = 1 << 24;
+/// Written by a function that keeps the symbol.
+pub(super) const KEEP: u16 = u16::MAX;
+/// Entry of a state and symbols without a function.
+const NO_TRANSITION: u32 = u32::MAX;
+
+/// A transition function with interned symbols and states.
+pub(super) struct Transition {
+    /// Index of the function in its state.
+    pub(super) index: usize,
+    pub(super) writes: Vec<u16>,
+    pub(super) moves: Vec<Direction>,
+    pub(super) next: u32,
+}
+
+/// The transition functions of a machine in a flat table from the state and
+/// the symbols under the heads, with states and symbols interned to
+/// integers.
+pub(super) struct TransitionTable {
+    /// Interned symbols, the blank first.
+    pub(super) symbols: Vec<String>,
+    pub(super) states: Vec<String>,
+    pub(super) end_states: Vec<bool>,
+    /// Index into `transitions` of every state and symbol combination, the
+    /// state is the most significant digit, then the bands in order.
+    table: Vec<u32>,
+    pub(super) transitions: Vec<Transition>,
+}
+
+impl TransitionTable {
+    pub(super) fn new(machine: &Machine) -> Result<Self, &'static str> {
+        let mut symbols = machine
+            .alphabet
+            .iter()
+            .filter(|symbol| *symbol != "_")
+            .cloned()
+            .collect::<Vec<_>>();
+        symbols.sort();
+        symbols.insert(0, "_".to_string());
+        if symbols.len() >= KEEP as usize {
+            return Err("alphabet too large");
+        }
+        let symbol_index = |symbol: &str| symbols.iter().position(|s| s == symbol);
+
+        let mut states = machine.states.keys().cloned().collect::<Vec<_>>();
+        states.sort();
+        let state_index = |name: &str| states.iter().position(|s| s == name).unwrap() as u32;
+
+        let combinations = (0..machine.size)
+            .try_fold(1usize, |n, _| n.checked_mul(symbols.len()))
+            .filter(|n| {
+                n.checked_mul(states.len())
+                    .map_or(false, |len| len <= MAX_TABLE)
+            })
+            .ok_or("transition table too large")?;
+
+        let mut table = Vec::with_capacity(states.len() * combinations);
+        let mut transitions = Vec::new();
+        for name in states.iter() {
+            let state = &machine.states[name];
+            let first = transitions.len();
+            for (index, f) in state.transition_functions.iter().enumerate() {
+                let writes = f
+                    .bands_actions
+                    .iter()
+                    .map(|(symbol, _)| match symbol.as_str() {
+                        WILDCARD => KEEP,
+                        symbol => symbol_index(symbol).unwrap() as u16,
+                    })
+                    .collect();
+                transitions.push(Transition {
+                    index,
+                    writes,
+                    moves: f.bands_actions.iter().map(|(_, d)| d.clone()).collect(),
+                    next: state_index(&f.next_state_name),
+                });
+            }
+            for combination in 0..combinations {
+                let mut read = vec![""; machine.size];
+                let mut rest = combination;
+                for band in (0..machine.size).rev() {
+                    read[band] = &symbols[rest % symbols.len()];
+                    rest /= symbols.len();
+                }
+                table.push(
+                    state
+                        .transition_functions
+                        .iter()
+                        .position(|f| f.matches(&read))
+                        .map_or(NO_TRANSITION, |index| (first + index) as u32),
+                );
+            }
+        }
+
+        Ok(Self {
+            end_states: states
+                .iter()
+                .map(|s| machine.states[s].is_end_state)
+                .collect(),
+            symbols,
+            states,
+            table,
+            transitions,
+        })
+    }
+
+    pub(super) fn symbol_index(&self, symbol: &str) -> Option<u16> {
+        self.symbols
+            .iter()
+            .position(|s| s == symbol)
+            .map(|index| index as u16)
+    }
+
+    pub(super) fn state_index(&self, name: &str) -> u32 {
+        self.states.iter().position(|s| s == name).unwrap() as u32
+    }
+
+    /// Index into `transitions` of the function for `state` and the symbols
+    /// under the heads.
+    #[inline]
+    pub(super) fn lookup(&self, state: u32, read: impl Iterator<Item = u16>) -> Option<usize> {
+        let n = self.symbols.len();
+        let key = read.fold(state as usize, |key, symbol| key * n + symbol as usize);
+        match self.table[key] {
+            NO_TRANSITION => None,
+            index => Some(index as usize),
+        }
+    }
+
+    /// Interned input symbols, `None` if one isn't in the alphabet.
+    pub(super) fn input(&self, input: &[String]) -> Option<Vec<u16>> {
+        input
+            .iter()
+            .map(|symbol| self.symbol_index(symbol))
+            .collect()
+    }
+
+    /// The step that executed the transition at `index` in `state` on `read`.
+    pub(super) fn simulation_step(
+        &self,
+        state: u32,
+        index: usize,
+        read: Vec<String>,
+    ) -> SimulationStep {
+        let transition = &self.transitions[index];
+        let actions = transition
+            .writes
+            .iter()
+            .zip(transition.moves.iter())
+            .zip(read.iter())
+            .map(|((write, direction), read)| {
+                let symbol = match *write {
+                    KEEP => read.clone(),
+                    write => self.symbols[write as usize].clone(),
+                };
+                (symbol, direction.clone())
+            })
+            .collect();
+        SimulationStep {
+            state: self.states[state as usize].clone(),
+            transition_function: (transition.index, actions),
+            read,
+        }
+    }
+}
+
+/// Like `Tape`, with interned symbols. The blank is 0.
+#[derive(Debug, Clone)]
+struct DenseTape {
+    cells: VecDeque<u16>,
+    offset: isize,
+    head: isize,
+    mode: TapeMode,
+}
+
+impl DenseTape {
+    fn new(input: Vec<u16>, mode: TapeMode) -> Self {
+        let mut cells = VecDeque::from(input);
+        if cells.is_empty() {
+            cells.push_back(0);
+        }
+        Self {
+            cells,
+            offset: 0,
+            head: 0,
+            mode,
+        }
+    }
+
+    fn index(&self) -> usize {
+        (self.head - self.offset) as usize
+    }
+
+    fn read(&self) -> u16 {
+        self.cells[self.index()]
+    }
+
+    fn write(&mut self, symbol: u16) {
+        let index = self.index();
+        self.cells[index] = symbol;
+    }
+
+    fn falls_off(&self, direction: &Direction) -> bool {
+        self.mode == TapeMode::LeftBounded && self.head == 0 && *direction == Direction::Left
+    }
+
+    fn move_head(&mut self, direction: &Direction) {
+        match direction {
+            Direction::Right => {
+                self.head += 1;
+                if self.index() == self.cells.len() {
+                    self.cells.push_back(0);
+                }
+            }
+            Direction::Left if self.head == self.offset => {
+                if self.mode == TapeMode::TwoWayInfinite {
+                    self.cells.push_front(0);
+                    self.offset -= 1;
+                    self.head -= 1;
+                }
+            }
+            Direction::Left => self.head -= 1,
+            Direction::Unchanged => {}
+        }
+    }
+}
+
+/// Runs a machine through a `TransitionTable`. Much faster than
+/// `MachineExecutor`, but without history, snapshots or statistics.
+pub struct DenseExecutor {
+    table: TransitionTable,
+    bands: Vec<DenseTape>,
+    state: u32,
+    steps: usize,
+}
+
+impl DenseExecutor {
+    pub fn new(machine: &Machine, input: Vec<String>) -> Result<Self, &'static str> {
+        let table = TransitionTable::new(machine)?;
+        let input = table.input(&input).ok_or("input symbol not in alphabet")?;
+        let mut bands = Vec::with_capacity(machine.size);
+        bands.push(DenseTape::new(input, machine.tape_mode));
+        bands.resize_with(machine.size, || DenseTape::new(vec![], machine.tape_mode));
+
+        Ok(Self {
+            state: table.state_index(&machine.start_state_name),
+            table,
+            bands,
+            steps: 0,
+        })
+    }
+
+    pub fn current_state_name(&self) -> &str {
+        &self.table.states[self.state as usize]
+    }
+
+    /// Number of executed steps.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Executes one transition, returns its index in `transitions`.
+    #[inline]
+    fn step(&mut self) -> Result<Option<usize>, FellOffTape> {
+        let index = match self
+            .table
+            .lookup(self.state, self.bands.iter().map(DenseTape::read))
+        {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let transition = &self.table.transitions[index];
+        if let Some(band) =
+            (0..self.bands.len()).find(|b| self.bands[*b].falls_off(&transition.moves[*b]))
+        {
+            return Err(FellOffTape { band });
+        }
+        for (band, (write, direction)) in self
+            .bands
+            .iter_mut()
+            .zip(transition.writes.iter().zip(transition.moves.iter()))
+        {
+            if *write != KEEP {
+                band.write(*write);
+            }
+            band.move_head(direction);
+        }
+        self.state = transition.next;
+        self.steps += 1;
+        Ok(Some(index))
+    }
+
+    /// Like `Simulator::run` without building a `SimulationStep` for every
+    /// step. The time limit is checked every `TIME_CHECK_INTERVAL` steps.
+    pub fn execute(&mut self, limits: &Limits) -> RunOutcome {
+        let start = Instant::now();
+        let mut steps = 0;
+        loop {
+            if self.table.end_states[self.state as usize] {
+                return RunOutcome::Accepted;
+            }
+            if limits.steps.map_or(false, |max| steps >= max) {
+                return RunOutcome::StepLimitExceeded;
+            }
+            if steps % TIME_CHECK_INTERVAL == 0
+                && limits
+                    .milliseconds
+                    .map_or(false, |max| start.elapsed() >= Duration::from_millis(max))
+            {
+                return RunOutcome::TimeLimitExceeded;
+            }
+            match self.step() {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    return RunOutcome::Rejected {
+                        symbols: self.read(),
+                    }
+                }
+                Err(FellOffTape { band }) => return RunOutcome::FellOffTape { band },
+            }
+            steps += 1;
+            if limits.cells.map_or(false, |max| self.cells() > max) {
+                return RunOutcome::TapeLimitExceeded;
+            }
+        }
+    }
+}
+
+impl Simulator for DenseExecutor {
+    fn next_step(&mut self) -> Result<Option<SimulationStep>, FellOffTape> {
+        let (state, read) = (self.state, self.read());
+        Ok(self
+            .step()?
+            .map(|index| self.table.simulation_step(state, index, read)))
+    }
+
+    fn is_end_state(&self) -> bool {
+        self.table.end_states[self.state as usize]
+    }
+
+    fn band_symbols(&self) -> Vec<Vec<String>> {
+        self.bands
+            .iter()
+            .map(|band| {
+                band.cells
+                    .iter()
+                    .map(|symbol| self.table.symbols[*symbol as usize].clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn heads(&self) -> Vec<isize> {
+        self.bands.iter().map(|band| band.head).collect()
+    }
+
+    fn read(&self) -> Vec<String> {
+        self.bands
+            .iter()
+            .map(|band| self.table.symbols[band.read() as usize].clone())
+            .collect()
+    }
+
+    fn cells(&self) -> usize {
+        self.bands.iter().map(|band| band.cells.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::tests::counter;
+    use crate::sim::MachineExecutor;
+    use crate::{compact, tmsim};
+
+    #[test]
+    fn dense_executor_matches() {
+        let busy_beaver = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        let marker = tmsim::from_tmsim(
+            "init: mark\naccept: done\n\
+             mark,0,1\ndone,*,*,-,-\n\
+             mark,1,_\nmark,*,1,>,>\n\
+             mark,_,_\ndone,*,*,-,-\n\
+             mark,*,*\nmark,*,*,>,>",
+        )
+        .unwrap();
+        let limits = Limits {
+            steps: Some(1000),
+            ..Limits::default()
+        };
+        let input = "0110".chars().map(String::from).collect::<Vec<_>>();
+        let runs = [
+            (busy_beaver.clone(), vec![]),
+            (
+                busy_beaver.clone().with_tape_mode(TapeMode::Clamped),
+                vec![],
+            ),
+            (busy_beaver.with_tape_mode(TapeMode::LeftBounded), vec![]),
+            (
+                compact::from_compact("1RB1LB_1LA0LC_1RZ1LD_1RD0RA").unwrap(),
+                vec![],
+            ),
+            (counter(), vec![]),
+            (marker, input),
+        ];
+
+        for (machine, input) in runs {
+            let mut executor = MachineExecutor::new(machine.clone(), input.clone());
+            let mut dense = DenseExecutor::new(&machine, input.clone()).unwrap();
+            assert_eq!(dense.execute(&limits), executor.run(&limits));
+            assert_eq!(dense.current_state_name(), executor.current_state_name());
+            assert_eq!(dense.band_symbols(), executor.band_symbols());
+            assert_eq!(dense.heads(), executor.heads());
+
+            let mut executor = MachineExecutor::new(machine.clone(), input.clone());
+            let mut dense = DenseExecutor::new(&machine, input).unwrap();
+            for _ in 0..100 {
+                match (dense.next_step(), executor.next_step()) {
+                    (Ok(Some(a)), Ok(Some(b))) => {
+                        assert_eq!(a.state(), b.state());
+                        assert_eq!(a.transition_index(), b.transition_index());
+                        assert_eq!(a.actions(), b.actions());
+                        assert_eq!(a.read(), b.read());
+                    }
+                    (a, b) => {
+                        assert_eq!(a.map(|step| step.is_none()), b.map(|step| step.is_none()));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
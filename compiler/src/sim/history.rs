@@ -0,0 +1,111 @@
+use super::{Bands, FellOffTape, MachineExecutor, Simulator, TapeUndo};
+
+/// Every this many steps the executor keeps a full copy of its configuration,
+/// seeking goes to the closest one instead of undoing every step.
+pub const CHECKPOINT_INTERVAL: usize = 1024;
+
+/// What a step changed, enough to take it back.
+#[derive(Debug, Clone)]
+pub(super) struct Undo {
+    /// The state the step started in.
+    state: String,
+    bands: Vec<TapeUndo>,
+}
+
+impl Undo {
+    pub(super) fn new(state: String, bands: Vec<TapeUndo>) -> Self {
+        Self { state, bands }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Checkpoint {
+    step: usize,
+    state: String,
+    bands: Bands,
+}
+
+impl Checkpoint {
+    pub(super) fn new(step: usize, state: String, bands: Bands) -> Self {
+        Self { step, state, bands }
+    }
+}
+
+impl MachineExecutor {
+    /// Records every step from the current configuration on, so `step_back`
+    /// and `seek` can go back to it. Without it a run doesn't keep anything
+    /// per step.
+    pub fn with_history(mut self) -> Self {
+        self.recording = true;
+        self.first_step = self.steps;
+        self.history.clear();
+        self.checkpoints = vec![Checkpoint::new(
+            self.steps,
+            self.current_state.clone(),
+            self.bands.clone(),
+        )];
+        self
+    }
+
+    /// Number of steps executed since the start of the run.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Takes back the last step, `false` where the history starts or without
+    /// a history.
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.pop() {
+            Some(undo) => undo,
+            None => return false,
+        };
+        for (band, undo) in self.bands.iter_mut().zip(undo.bands.iter()) {
+            band.undo(undo);
+        }
+        self.current_state = undo.state;
+        self.steps -= 1;
+        let steps = self.steps;
+        self.checkpoints
+            .retain(|checkpoint| checkpoint.step <= steps);
+        true
+    }
+
+    /// Moves to the configuration after `step` steps, going back through the
+    /// recorded history or running forward. Returns the step that was reached,
+    /// which is earlier than `step` if the machine stops before and never
+    /// before the history starts.
+    pub fn seek(&mut self, step: usize) -> Result<usize, FellOffTape> {
+        let first = if self.recording {
+            self.first_step
+        } else {
+            self.steps
+        };
+        let step = step.max(first);
+        if step < self.steps() {
+            let checkpoint = self
+                .checkpoints
+                .iter()
+                .rev()
+                .find(|checkpoint| checkpoint.step <= step)
+                .unwrap();
+            if self.steps() - step > step - checkpoint.step {
+                let checkpoint = checkpoint.clone();
+                self.history.truncate(checkpoint.step - self.first_step);
+                self.checkpoints
+                    .retain(|other| other.step <= checkpoint.step);
+                self.current_state = checkpoint.state;
+                self.bands = checkpoint.bands;
+                self.steps = checkpoint.step;
+            }
+            while self.steps() > step {
+                self.step_back();
+            }
+        }
+        while self.steps() < step {
+            if self.next_step()?.is_none() {
+                break;
+            }
+        }
+        Ok(self.steps())
+    }
+}
@@ -0,0 +1,671 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::tm::*;
+
+mod accelerated;
+mod breakpoint;
+mod cycles;
+mod dense;
+mod history;
+mod run;
+mod snapshot;
+mod stats;
+mod tape;
+pub mod trace;
+
+pub use accelerated::AcceleratedExecutor;
+pub use breakpoint::{Breakpoint, Predicate};
+pub use cycles::CycleDetector;
+pub use dense::DenseExecutor;
+pub use history::CHECKPOINT_INTERVAL;
+use history::{Checkpoint, Undo};
+pub use run::{Limits, RunOutcome};
+pub use snapshot::{machine_hash, Snapshot};
+pub use stats::Statistics;
+use stats::StatisticsCollector;
+pub use tape::{FellOffTape, Tape, TapeUndo};
+
+type Bands = Vec<Tape>;
+
+/// `execute` of the dense and the accelerated executor only looks at the
+/// clock every this many steps, macro steps for the accelerated one.
+const TIME_CHECK_INTERVAL: usize = 1 << 16;
+
+/// A way of running a `Machine`. Tools only need this to drive a run, so
+/// they work with any simulator.
+pub trait Simulator {
+    /// Executes one transition, `None` if no transition matches.
+    fn next_step(&mut self) -> Result<Option<SimulationStep>, FellOffTape>;
+
+    /// Whether the current state is an end state of the machine.
+    fn is_end_state(&self) -> bool;
+
+    /// The stored cells of every band, from left to right.
+    fn band_symbols(&self) -> Vec<Vec<String>>;
+
+    /// The head position of every band, cell 0 is where the input starts.
+    fn heads(&self) -> Vec<isize>;
+
+    /// The symbol under the head of every band.
+    fn read(&self) -> Vec<String>;
+
+    /// The number of stored cells over all bands.
+    fn cells(&self) -> usize;
+
+    /// Runs until an end state is reached, no transition matches or a limit
+    /// is exceeded.
+    fn run(&mut self, limits: &Limits) -> RunOutcome
+    where
+        Self: Sized,
+    {
+        self.run_with(limits, |_| {})
+    }
+
+    /// Like `run`, passing every executed step to `on_step`.
+    fn run_with(&mut self, limits: &Limits, mut on_step: impl FnMut(SimulationStep)) -> RunOutcome
+    where
+        Self: Sized,
+    {
+        self.run_until(limits, |_, step| {
+            on_step(step);
+            None
+        })
+    }
+
+    /// Like `run`, `on_step` gets the simulator after every step and stops the
+    /// run by returning an outcome.
+    fn run_until(
+        &mut self,
+        limits: &Limits,
+        mut on_step: impl FnMut(&Self, SimulationStep) -> Option<RunOutcome>,
+    ) -> RunOutcome
+    where
+        Self: Sized,
+    {
+        let start = Instant::now();
+        let mut steps = 0;
+        loop {
+            if self.is_end_state() {
+                return RunOutcome::Accepted;
+            }
+            if limits.steps.map_or(false, |max| steps >= max) {
+                return RunOutcome::StepLimitExceeded;
+            }
+            if limits
+                .milliseconds
+                .map_or(false, |max| start.elapsed() >= Duration::from_millis(max))
+            {
+                return RunOutcome::TimeLimitExceeded;
+            }
+            match self.next_step() {
+                Ok(Some(step)) => {
+                    if let Some(outcome) = on_step(self, step) {
+                        return outcome;
+                    }
+                }
+                Ok(None) => {
+                    return RunOutcome::Rejected {
+                        symbols: self.read(),
+                    }
+                }
+                Err(FellOffTape { band }) => return RunOutcome::FellOffTape { band },
+            }
+            steps += 1;
+            if limits.cells.map_or(false, |max| self.cells() > max) {
+                return RunOutcome::TapeLimitExceeded;
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct MachineExecutor {
+    machine: Machine,
+    bands: Bands,
+    current_state: String,
+    steps: usize,
+    /// Steps executed before the history starts, when resumed from a
+    /// snapshot or recorded from later on.
+    first_step: usize,
+    /// Whether steps are recorded so they can be taken back, see
+    /// `with_history`.
+    #[serde(skip)]
+    recording: bool,
+    #[serde(skip)]
+    history: Vec<Undo>,
+    #[serde(skip)]
+    checkpoints: Vec<Checkpoint>,
+    #[serde(skip)]
+    statistics: Option<StatisticsCollector>,
+}
+
+impl MachineExecutor {
+    pub fn new(machine: Machine, input: Vec<String>) -> Self {
+        let mut bands = vec![Tape::new(input, machine.tape_mode)];
+        bands.resize_with(machine.size, || Tape::new(vec![], machine.tape_mode));
+        Self {
+            current_state: machine.start_state_name.clone(),
+            machine,
+            bands,
+            steps: 0,
+            first_step: 0,
+            recording: false,
+            history: Vec::new(),
+            checkpoints: Vec::new(),
+            statistics: None,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn bands(&self) -> &[Tape] {
+        &self.bands
+    }
+
+    pub fn current_state(&self) -> &State {
+        &self.machine.states[&self.current_state]
+    }
+
+    pub fn current_state_name(&self) -> &str {
+        &self.current_state
+    }
+
+    /// Starts collecting statistics from the current configuration on. Steps
+    /// that are taken back stay counted.
+    pub fn collect_statistics(&mut self) {
+        let heads = self.heads();
+        self.statistics = Some(StatisticsCollector::new(
+            &self.machine,
+            &self.current_state,
+            &heads,
+        ));
+    }
+
+    /// `None` unless `collect_statistics` was called.
+    pub fn statistics(&self) -> Option<&Statistics> {
+        self.statistics
+            .as_ref()
+            .map(StatisticsCollector::statistics)
+    }
+}
+
+impl Simulator for MachineExecutor {
+    fn next_step(&mut self) -> Result<Option<SimulationStep>, FellOffTape> {
+        if let Some((index, transition)) = self
+            .current_state()
+            .transition_functions
+            .iter()
+            .enumerate()
+            .find(|i| self.function_matches_band(i.1))
+            .map(|(index, transition)| (index, transition.clone()))
+        {
+            let read = self.read();
+            let state = self.current_state.clone();
+            let actions = self.apply_transition_to_band(&transition)?;
+            let step = SimulationStep {
+                state,
+                transition_function: (index, actions),
+                read,
+            };
+            let heads = self.heads();
+            if let Some(statistics) = self.statistics.as_mut() {
+                statistics.record(&step, &self.current_state, &heads);
+            }
+            return Ok(Some(step));
+        }
+        Ok(None)
+    }
+
+    fn is_end_state(&self) -> bool {
+        self.current_state().is_end_state
+    }
+
+    fn band_symbols(&self) -> Vec<Vec<String>> {
+        self.bands.iter().map(Tape::symbols).collect()
+    }
+
+    fn heads(&self) -> Vec<isize> {
+        self.bands.iter().map(Tape::head).collect()
+    }
+
+    fn read(&self) -> Vec<String> {
+        self.bands
+            .iter()
+            .map(|band| band.read().to_string())
+            .collect()
+    }
+
+    fn cells(&self) -> usize {
+        self.bands.iter().map(Tape::len).sum()
+    }
+}
+
+impl MachineExecutor {
+    /// Returns the actions with the symbols that were actually written.
+    fn apply_transition_to_band(
+        &mut self,
+        transition: &TransitionFunction,
+    ) -> Result<Vec<(String, Direction)>, FellOffTape> {
+        if let Some(band) = (0..self.machine.size)
+            .find(|x| self.bands[*x].falls_off(&transition.bands_actions[*x].1))
+        {
+            return Err(FellOffTape { band });
+        }
+        let undo = self.recording.then(|| {
+            Undo::new(
+                self.current_state.clone(),
+                self.bands.iter().map(Tape::undo_info).collect(),
+            )
+        });
+        let mut actions = Vec::with_capacity(self.machine.size);
+        for x in 0..self.machine.size {
+            let band = self.bands.get_mut(x).unwrap();
+            let direction = &transition.bands_actions.get(x).unwrap().1;
+            let symbol = transition.written(x, band.read()).to_string();
+            band.write(symbol.clone());
+            band.move_head(direction);
+            actions.push((symbol, direction.clone()));
+        }
+        self.current_state = transition.next_state_name.clone();
+        self.steps += 1;
+        if let Some(undo) = undo {
+            self.history.push(undo);
+            if self.steps % CHECKPOINT_INTERVAL == 0 {
+                self.checkpoints.push(Checkpoint::new(
+                    self.steps,
+                    self.current_state.clone(),
+                    self.bands.clone(),
+                ));
+            }
+        }
+        Ok(actions)
+    }
+
+    fn function_matches_band(&self, transition: &TransitionFunction) -> bool {
+        let read = self.bands.iter().map(Tape::read).collect::<Vec<_>>();
+        transition.matches(&read)
+    }
+}
+#[derive(Serialize, Debug)]
+pub struct SimulationStep {
+    /// The state the step started in.
+    state: String,
+    transition_function: (usize, Vec<(String, Direction)>),
+    /// The symbols under the heads before the step.
+    read: Vec<String>,
+}
+
+impl SimulationStep {
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// Index of the transition function in its state.
+    pub fn transition_index(&self) -> usize {
+        self.transition_function.0
+    }
+
+    /// The written symbols and head moves of every band.
+    pub fn actions(&self) -> &[(String, Direction)] {
+        &self.transition_function.1
+    }
+
+    pub fn read(&self) -> &[String] {
+        &self.read
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{compact, tmsim};
+
+    fn run(machine: Machine, input: Vec<String>) -> MachineExecutor {
+        let mut executor = MachineExecutor::new(machine, input);
+        while executor.next_step().unwrap().is_some() {}
+        executor
+    }
+
+    /// A binary counter that never stops.
+    pub(super) fn counter() -> Machine {
+        tmsim::from_tmsim(
+            "init: right\naccept: done\n\
+             right,0\nright,0,>\nright,1\nright,1,>\nright,_\ncarry,_,<\n\
+             carry,1\ncarry,0,<\ncarry,0\nright,1,>\ncarry,_\nright,1,>",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn create_simple_machine() {
+        let mut states = HashMap::new();
+        states.insert(
+            "q0".to_string(),
+            State {
+                transition_functions: vec![
+                    TransitionFunction::new(
+                        "q0".to_string(),
+                        vec!["0".to_string()],
+                        vec![("1".to_string(), Direction::Right)],
+                        "q0".to_string(),
+                    ),
+                    TransitionFunction::new(
+                        "q0".to_string(),
+                        vec!['1'.to_string()],
+                        vec![('1'.to_string(), Direction::Right)],
+                        "q0".to_string(),
+                    ),
+                    TransitionFunction::new(
+                        "q0".to_string(),
+                        vec!['_'.to_string()],
+                        vec![('_'.to_string(), Direction::Unchanged)],
+                        "q1".to_string(),
+                    ),
+                ],
+                is_end_state: false,
+            },
+        );
+        states.insert(
+            "q1".to_string(),
+            State {
+                transition_functions: vec![],
+                is_end_state: true,
+            },
+        );
+        let machine = Machine::new(
+            HashSet::from(['0'.to_string(), '1'.to_string()]),
+            1,
+            states,
+            "q0".to_string(),
+        )
+        .unwrap();
+        let mut machine_executor = MachineExecutor::new(
+            machine,
+            vec!["0".to_string(), "0".to_string(), "0".to_string()],
+        );
+        println!("initial state{:?}", machine_executor.bands);
+        while let Some(simulation_step) = machine_executor.next_step().unwrap() {
+            println!("{:?}", simulation_step);
+        }
+        assert!(machine_executor.is_end_state());
+        let bands = machine_executor.bands.first().unwrap();
+        assert_eq!(bands.symbols().concat(), "111_");
+    }
+
+    #[test]
+    fn tape_modes() {
+        let busy_beaver = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+
+        let two_way = run(busy_beaver.clone(), vec![]);
+        assert!(two_way.is_end_state());
+        assert_eq!(two_way.bands[0].symbols().concat(), "1111");
+        assert_eq!(two_way.bands[0].offset(), -2);
+        assert_eq!(two_way.bands[0].head(), 0);
+
+        let clamped = run(
+            busy_beaver.clone().with_tape_mode(TapeMode::Clamped),
+            vec![],
+        );
+        assert!(clamped.is_end_state());
+        assert_eq!(clamped.bands[0].symbols().concat(), "11");
+        assert_eq!(clamped.bands[0].head(), 1);
+
+        let mut bounded =
+            MachineExecutor::new(busy_beaver.with_tape_mode(TapeMode::LeftBounded), vec![]);
+        assert!(bounded.next_step().unwrap().is_some());
+        assert!(bounded.next_step().unwrap().is_some());
+        assert_eq!(bounded.next_step().unwrap_err(), FellOffTape { band: 0 });
+    }
+
+    #[test]
+    fn stops_when_no_function_matches() {
+        let stuck = tmsim::from_tmsim("init: a\naccept: done\na,_\nb,1,>\nb,_\na,_,<").unwrap();
+        let mut executor = MachineExecutor::new(stuck, vec![]);
+        let mut steps = 0;
+        while executor.next_step().unwrap().is_some() {
+            steps += 1;
+        }
+        assert_eq!(steps, 2);
+        assert!(!executor.is_end_state());
+        assert_eq!(executor.heads(), vec![0]);
+        assert_eq!(executor.band_symbols()[0].concat(), "1_");
+    }
+
+    #[test]
+    fn run_outcomes() {
+        let busy_beaver = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        let stuck = tmsim::from_tmsim("init: a\naccept: done\na,_\nb,1,>\nb,_\na,_,<").unwrap();
+        let forever = compact::from_compact("1RA1RA").unwrap();
+        let outcome = |machine: &Machine, limits: Limits| {
+            MachineExecutor::new(machine.clone(), vec![]).run(&limits)
+        };
+
+        assert_eq!(
+            outcome(&busy_beaver, Limits::default()),
+            RunOutcome::Accepted
+        );
+        assert_eq!(
+            outcome(&stuck, Limits::default()),
+            RunOutcome::Rejected {
+                symbols: vec!["1".to_string()]
+            }
+        );
+        let steps = Limits {
+            steps: Some(100),
+            ..Limits::default()
+        };
+        assert_eq!(outcome(&forever, steps), RunOutcome::StepLimitExceeded);
+        let cells = Limits {
+            cells: Some(10),
+            ..Limits::default()
+        };
+        assert_eq!(outcome(&forever, cells), RunOutcome::TapeLimitExceeded);
+        let time = Limits {
+            milliseconds: Some(0),
+            ..Limits::default()
+        };
+        assert_eq!(outcome(&forever, time), RunOutcome::TimeLimitExceeded);
+        assert_eq!(
+            outcome(
+                &busy_beaver.with_tape_mode(TapeMode::LeftBounded),
+                Limits::default()
+            ),
+            RunOutcome::FellOffTape { band: 0 }
+        );
+    }
+
+    #[test]
+    fn seeking_restores_every_configuration() {
+        let counter = counter();
+        let steps = 3 * CHECKPOINT_INTERVAL;
+        let mut executor = MachineExecutor::new(counter, vec![]).with_history();
+        let mut configurations = vec![serde_json::to_value(&executor).unwrap()];
+        for _ in 0..steps {
+            executor.next_step().unwrap().unwrap();
+            configurations.push(serde_json::to_value(&executor).unwrap());
+        }
+
+        for step in [steps - 1, 10, 2500, CHECKPOINT_INTERVAL, 0, steps] {
+            assert_eq!(executor.seek(step).unwrap(), step);
+            assert_eq!(
+                serde_json::to_value(&executor).unwrap(),
+                configurations[step]
+            );
+        }
+        while executor.step_back() {
+            assert_eq!(
+                serde_json::to_value(&executor).unwrap(),
+                configurations[executor.steps()]
+            );
+        }
+        assert_eq!(executor.steps(), 0);
+    }
+
+    #[test]
+    fn resume_from_snapshot() {
+        let counter = counter();
+        let mut direct = MachineExecutor::new(counter.clone(), vec![]).with_history();
+        direct.seek(1500).unwrap();
+        let json = serde_json::to_string(&direct.snapshot()).unwrap();
+        direct.seek(2000).unwrap();
+
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        let mut resumed = MachineExecutor::from_snapshot(counter.clone(), snapshot.clone())
+            .unwrap()
+            .with_history();
+        assert_eq!(resumed.seek(2000).unwrap(), 2000);
+        assert_eq!(resumed.snapshot(), direct.snapshot());
+        assert_eq!(resumed.seek(0).unwrap(), 1500);
+        assert!(!resumed.step_back());
+
+        let other = counter.clone().with_tape_mode(TapeMode::Clamped);
+        assert!(MachineExecutor::from_snapshot(other, snapshot.clone()).is_err());
+
+        // a machine edited in the file, with the hash to match
+        let mut broken = counter;
+        for state in broken.states.values_mut() {
+            for f in state.transition_functions.iter_mut() {
+                f.next_state_name = "missing".to_string();
+            }
+        }
+        let snapshot = Snapshot {
+            machine_hash: machine_hash(&broken),
+            ..snapshot
+        };
+        assert_eq!(
+            MachineExecutor::from_snapshot(broken, snapshot).err(),
+            Some("next state not found")
+        );
+    }
+
+    #[test]
+    fn history_is_opt_in() {
+        let mut executor = MachineExecutor::new(counter(), vec![]);
+        executor.seek(3 * CHECKPOINT_INTERVAL).unwrap();
+        assert!(!executor.step_back());
+        assert_eq!(executor.seek(0).unwrap(), 3 * CHECKPOINT_INTERVAL);
+
+        let mut executor = executor.with_history();
+        executor.seek(4 * CHECKPOINT_INTERVAL).unwrap();
+        assert!(executor.step_back());
+        assert_eq!(executor.seek(0).unwrap(), 3 * CHECKPOINT_INTERVAL);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let outcome = |breakpoint: Breakpoint| {
+            let mut executor = MachineExecutor::new(counter(), vec![]);
+            let outcome = executor.run_until_breakpoint(&[breakpoint], &Limits::default(), |_| {});
+            (outcome, executor.steps())
+        };
+        let hit = RunOutcome::Breakpoint { index: 0 };
+
+        // the counter moves left onto a new cell and carries, writes its first
+        // 1 in step 2 and carries over it in step 4
+        assert_eq!(
+            outcome(Breakpoint::State("carry".to_string())),
+            (hit.clone(), 1)
+        );
+        assert_eq!(
+            outcome(Breakpoint::Transition {
+                state: "carry".to_string(),
+                index: 0
+            }),
+            (hit.clone(), 4)
+        );
+        assert_eq!(
+            outcome(Breakpoint::Read {
+                band: 0,
+                symbol: "1".to_string()
+            }),
+            (hit.clone(), 4)
+        );
+        assert_eq!(
+            outcome(Breakpoint::Write {
+                band: 0,
+                symbol: "1".to_string()
+            }),
+            (hit.clone(), 2)
+        );
+        assert_eq!(
+            outcome(Breakpoint::Head {
+                band: 0,
+                position: -2
+            }),
+            (hit.clone(), 4)
+        );
+        let predicate = Predicate(Arc::new(|executor: &MachineExecutor| executor.cells() == 3));
+        assert_eq!(outcome(Breakpoint::Predicate(predicate)), (hit, 4));
+
+        // carrying over a 1 in steps 4 and 10 stays in the state
+        let mut executor = MachineExecutor::new(counter(), vec![]);
+        let entered = (0..4)
+            .map(|_| {
+                let breakpoints = [Breakpoint::State("carry".to_string())];
+                executor.run_until_breakpoint(&breakpoints, &Limits::default(), |_| {});
+                executor.steps()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(entered, vec![1, 3, 7, 9]);
+    }
+
+    #[test]
+    fn statistics() {
+        let busy_beaver = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        let mut executor = MachineExecutor::new(busy_beaver, vec![]);
+        executor.collect_statistics();
+        executor.run(&Limits::default());
+
+        let statistics = executor.statistics().unwrap();
+        assert_eq!(statistics.steps, 6);
+        assert_eq!(statistics.min_head, vec![-2]);
+        assert_eq!(statistics.max_head, vec![1]);
+        assert_eq!(statistics.cells_touched, 4);
+        assert_eq!(statistics.state_visits["A"], 3);
+        assert_eq!(statistics.state_visits["B"], 3);
+        assert_eq!(statistics.state_visits["Z"], 1);
+        assert_eq!(statistics.transition_firings["A"].iter().sum::<usize>(), 3);
+        assert_eq!(statistics.transition_firings["B"].iter().sum::<usize>(), 3);
+
+        // the origin of a function doesn't have to match its state
+        let mut busy_beaver = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        for state in busy_beaver.states.values_mut() {
+            for f in state.transition_functions.iter_mut() {
+                f.origin = "elsewhere".to_string();
+            }
+        }
+        let mut executor = MachineExecutor::new(busy_beaver, vec![]);
+        executor.collect_statistics();
+        executor.run(&Limits::default());
+        assert_eq!(executor.statistics().unwrap(), statistics);
+    }
+
+    #[test]
+    fn every_band_has_to_match() {
+        // marks the 1s of the first band on the second band, the first
+        // function never applies since the second band is blank under the head
+        let machine = tmsim::from_tmsim(
+            "init: mark\naccept: done\n\
+             mark,0,1\ndone,*,*,-,-\n\
+             mark,1,_\nmark,*,1,>,>\n\
+             mark,_,_\ndone,*,*,-,-\n\
+             mark,*,*\nmark,*,*,>,>",
+        )
+        .unwrap();
+        let input = "0110".chars().map(String::from).collect::<Vec<_>>();
+
+        for machine in [machine.clone(), machine.expand_wildcards()] {
+            let executor = run(machine, input.clone());
+            assert!(executor.is_end_state());
+            assert_eq!(executor.bands[0].symbols().concat(), "0110_");
+            assert_eq!(executor.bands[1].symbols().concat(), "_11__");
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+/// Budgets of a run, `None` means unlimited.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of executed transitions.
+    pub steps: Option<usize>,
+    /// Maximum number of stored cells over all bands.
+    pub cells: Option<usize>,
+    /// Maximum wall-clock time of the run.
+    pub milliseconds: Option<u64>,
+}
+
+/// Why a run stopped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// An end state was reached.
+    Accepted,
+    /// No transition matches the symbols under the heads of a state that
+    /// isn't an end state.
+    Rejected {
+        symbols: Vec<String>,
+    },
+    StepLimitExceeded,
+    TapeLimitExceeded,
+    TimeLimitExceeded,
+    /// A head moved left of cell 0 on a left bounded band.
+    FellOffTape {
+        band: usize,
+    },
+    /// The configuration at step `start` comes back every `period` steps,
+    /// so the run never halts.
+    Cycle {
+        start: usize,
+        period: usize,
+    },
+    /// From step `start` on, the run repeats every `period` steps moved by
+    /// `shift` cells into the blank part of the band, so it never halts.
+    TranslatedCycle {
+        start: usize,
+        period: usize,
+        shift: isize,
+    },
+    /// The breakpoint at `index` was hit.
+    Breakpoint {
+        index: usize,
+    },
+}
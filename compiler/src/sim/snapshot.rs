@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use super::{MachineExecutor, Tape};
+use crate::tm::Machine;
+
+/// The configuration of a run, enough to resume it on the same machine.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// `machine_hash` of the machine the run belongs to.
+    pub machine_hash: u64,
+    pub state: String,
+    pub bands: Vec<Tape>,
+    pub steps: usize,
+}
+
+/// FNV-1a hash of the machine in a canonical form, so it doesn't depend on
+/// the order of the hash maps and stays the same across builds.
+pub fn machine_hash(machine: &Machine) -> u64 {
+    let mut alphabet = machine.alphabet.iter().collect::<Vec<_>>();
+    alphabet.sort();
+    let mut states = machine.states.iter().collect::<Vec<_>>();
+    states.sort_by_key(|(name, _)| *name);
+
+    let mut canonical = format!(
+        "{:?}\n{}\n{}\n{:?}\n",
+        alphabet, machine.size, machine.start_state_name, machine.tape_mode
+    );
+    for (name, state) in states {
+        canonical.push_str(&format!("{:?} {}\n", name, state.is_end_state));
+        for f in state.transition_functions.iter() {
+            canonical.push_str(&format!(
+                "{:?} {:?} {:?}\n",
+                f.bands_requirements, f.bands_actions, f.next_state_name
+            ));
+        }
+    }
+
+    canonical.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl MachineExecutor {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            machine_hash: machine_hash(&self.machine),
+            state: self.current_state.clone(),
+            bands: self.bands.clone(),
+            steps: self.steps(),
+        }
+    }
+
+    /// Resumes a run from `snapshot`. A history started with `with_history`
+    /// starts at the snapshot, so stepping back stops there. The machine goes
+    /// through the checks of `Machine::new` again, it may come from a file.
+    pub fn from_snapshot(machine: Machine, snapshot: Snapshot) -> Result<Self, &'static str> {
+        if snapshot.machine_hash != machine_hash(&machine) {
+            return Err("snapshot belongs to a different machine");
+        }
+        let tape_mode = machine.tape_mode;
+        let machine = Machine::new(
+            machine.alphabet,
+            machine.size,
+            machine.states,
+            machine.start_state_name,
+        )?
+        .with_tape_mode(tape_mode);
+        if !machine.states.contains_key(&snapshot.state) {
+            return Err("snapshot state not found");
+        }
+        if snapshot.bands.len() != machine.size {
+            return Err("number of bands doesn't match the machine band size");
+        }
+        if snapshot
+            .bands
+            .iter()
+            .any(|band| band.mode() != machine.tape_mode || !band.is_consistent())
+        {
+            return Err("snapshot band is inconsistent");
+        }
+
+        Ok(Self {
+            machine,
+            bands: snapshot.bands,
+            current_state: snapshot.state,
+            steps: snapshot.steps,
+            first_step: snapshot.steps,
+            recording: false,
+            history: Vec::new(),
+            checkpoints: Vec::new(),
+            statistics: None,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::SimulationStep;
+use crate::tm::Machine;
+
+/// Numbers about a run, to compare how efficient machines are.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+    pub steps: usize,
+    /// Leftmost and rightmost head position of every band.
+    pub min_head: Vec<isize>,
+    pub max_head: Vec<isize>,
+    /// Number of distinct cells a head was on, over all bands.
+    pub cells_touched: usize,
+    /// How often each state was entered, the start state counts once for
+    /// the start of the run.
+    pub state_visits: BTreeMap<String, usize>,
+    /// How often each transition function fired, by state and index.
+    pub transition_firings: BTreeMap<String, Vec<usize>>,
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "steps: {}", self.steps)?;
+        writeln!(f, "cells touched: {}", self.cells_touched)?;
+        for (band, (min, max)) in self.min_head.iter().zip(self.max_head.iter()).enumerate() {
+            writeln!(f, "band {} head: {} to {}", band, min, max)?;
+        }
+        for (state, visits) in self.state_visits.iter() {
+            writeln!(f, "state {}: {} visits", state, visits)?;
+            for (index, firings) in self.transition_firings[state].iter().enumerate() {
+                writeln!(f, "    transition {}: {} firings", index, firings)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps `Statistics` up to date while the executor runs.
+#[derive(Debug, Clone)]
+pub(super) struct StatisticsCollector {
+    statistics: Statistics,
+    touched: HashSet<(usize, isize)>,
+}
+
+impl StatisticsCollector {
+    pub(super) fn new(machine: &Machine, state: &str, heads: &[isize]) -> Self {
+        let mut statistics = Statistics {
+            min_head: heads.to_vec(),
+            max_head: heads.to_vec(),
+            state_visits: machine
+                .states
+                .keys()
+                .map(|name| (name.clone(), 0))
+                .collect(),
+            transition_firings: machine
+                .states
+                .iter()
+                .map(|(name, state)| (name.clone(), vec![0; state.transition_functions.len()]))
+                .collect(),
+            ..Statistics::default()
+        };
+        *statistics.state_visits.get_mut(state).unwrap() += 1;
+        let mut collector = Self {
+            statistics,
+            touched: HashSet::new(),
+        };
+        collector.touch(heads);
+        collector
+    }
+
+    fn touch(&mut self, heads: &[isize]) {
+        for (band, head) in heads.iter().enumerate() {
+            self.touched.insert((band, *head));
+            self.statistics.min_head[band] = self.statistics.min_head[band].min(*head);
+            self.statistics.max_head[band] = self.statistics.max_head[band].max(*head);
+        }
+        self.statistics.cells_touched = self.touched.len();
+    }
+
+    pub(super) fn record(&mut self, step: &SimulationStep, state: &str, heads: &[isize]) {
+        self.statistics.steps += 1;
+        *self.statistics.state_visits.get_mut(state).unwrap() += 1;
+        self.statistics
+            .transition_firings
+            .get_mut(step.state())
+            .unwrap()[step.transition_index()] += 1;
+        self.touch(heads);
+    }
+
+    pub(super) fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+}
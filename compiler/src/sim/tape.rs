@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tm::{Direction, TapeMode};
+
+/// A band that grows in whichever direction the head moves. Only the cells
+/// that were visited or hold input are stored, `offset` is the position of
+/// the leftmost stored cell.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tape {
+    cells: VecDeque<String>,
+    offset: isize,
+    head: isize,
+    mode: TapeMode,
+}
+
+impl Tape {
+    pub fn new(input: Vec<String>, mode: TapeMode) -> Self {
+        let mut cells = VecDeque::from(input);
+        if cells.is_empty() {
+            cells.push_back("_".to_string());
+        }
+        Self {
+            cells,
+            offset: 0,
+            head: 0,
+            mode,
+        }
+    }
+
+    fn index(&self) -> usize {
+        (self.head - self.offset) as usize
+    }
+
+    pub fn head(&self) -> isize {
+        self.head
+    }
+
+    pub fn mode(&self) -> TapeMode {
+        self.mode
+    }
+
+    /// Position of the leftmost stored cell.
+    pub fn offset(&self) -> isize {
+        self.offset
+    }
+
+    pub fn read(&self) -> &str {
+        &self.cells[self.index()]
+    }
+
+    /// The symbol at `position`, the blank for cells that aren't stored.
+    pub fn symbol_at(&self, position: isize) -> &str {
+        let index = position - self.offset;
+        if index < 0 || index as usize >= self.cells.len() {
+            "_"
+        } else {
+            &self.cells[index as usize]
+        }
+    }
+
+    pub fn write(&mut self, symbol: String) {
+        let index = self.index();
+        self.cells[index] = symbol;
+    }
+
+    /// Whether moving in `direction` leaves a left bounded band.
+    pub fn falls_off(&self, direction: &Direction) -> bool {
+        self.mode == TapeMode::LeftBounded && self.head == 0 && *direction == Direction::Left
+    }
+
+    pub fn move_head(&mut self, direction: &Direction) {
+        match direction {
+            Direction::Right => {
+                self.head += 1;
+                if self.index() == self.cells.len() {
+                    self.cells.push_back("_".to_string());
+                }
+            }
+            Direction::Left if self.head == self.offset => {
+                if self.mode == TapeMode::TwoWayInfinite {
+                    self.cells.push_front("_".to_string());
+                    self.offset -= 1;
+                    self.head -= 1;
+                }
+            }
+            Direction::Left => self.head -= 1,
+            Direction::Unchanged => {}
+        }
+    }
+
+    /// Whether the head is on a stored cell and the band only grew left if
+    /// its mode allows it, which a deserialized band doesn't guarantee.
+    pub fn is_consistent(&self) -> bool {
+        let len = self.cells.len() as isize;
+        (self.offset == 0 || self.mode == TapeMode::TwoWayInfinite)
+            && self.offset <= self.head
+            && self.head < self.offset + len
+    }
+
+    /// The number of stored cells, a band always stores at least one cell.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// What the next write and move can change, so they can be undone.
+    pub fn undo_info(&self) -> TapeUndo {
+        TapeUndo {
+            symbol: self.read().to_string(),
+            head: self.head,
+            offset: self.offset,
+            len: self.cells.len(),
+        }
+    }
+
+    /// Restores the band to how it was when `undo` was taken, cells that were
+    /// added since are dropped again.
+    pub fn undo(&mut self, undo: &TapeUndo) {
+        while self.offset < undo.offset {
+            self.cells.pop_front();
+            self.offset += 1;
+        }
+        self.cells.truncate(undo.len);
+        self.head = undo.head;
+        self.write(undo.symbol.clone());
+    }
+
+    /// The stored cells from left to right.
+    pub fn symbols(&self) -> Vec<String> {
+        self.cells.iter().cloned().collect()
+    }
+}
+
+/// A band before a step, see `Tape::undo_info`.
+#[derive(Debug, Clone)]
+pub struct TapeUndo {
+    symbol: String,
+    head: isize,
+    offset: isize,
+    len: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FellOffTape {
+    pub band: usize,
+}
+
+impl fmt::Display for FellOffTape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "head of band {} moved left of cell 0", self.band)
+    }
+}
+
+impl std::error::Error for FellOffTape {}
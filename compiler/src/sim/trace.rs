@@ -0,0 +1,224 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Limits, MachineExecutor, RunOutcome, SimulationStep, Simulator};
+use crate::tm::Machine;
+
+/// One line of a trace file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Index of the step, starting at 0.
+    pub step: usize,
+    /// The state the step started in.
+    pub state: String,
+    /// Index of the transition function in its state.
+    pub transition: usize,
+    pub read: Vec<String>,
+    pub written: Vec<String>,
+    /// Head positions after the step.
+    pub heads: Vec<isize>,
+}
+
+impl TraceEntry {
+    pub fn new(step: usize, simulation_step: &SimulationStep, heads: Vec<isize>) -> Self {
+        Self {
+            step,
+            state: simulation_step.state().to_string(),
+            transition: simulation_step.transition_index(),
+            read: simulation_step.read().to_vec(),
+            written: simulation_step
+                .actions()
+                .iter()
+                .map(|(symbol, _)| symbol.clone())
+                .collect(),
+            heads,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl TraceError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+/// Writes one JSON object per step.
+pub struct TraceWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn write(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, entry)?;
+        self.out.write_all(b"\n")
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Runs `simulator` like `Simulator::run`, writing every step. The run isn't
+/// stopped by a failed write, the first error is returned after it.
+pub fn record<S: Simulator, W: Write>(
+    simulator: &mut S,
+    limits: &Limits,
+    writer: &mut TraceWriter<W>,
+) -> io::Result<RunOutcome> {
+    let mut result = Ok(());
+    let mut index = 0;
+    let outcome = simulator.run_until(limits, |simulator, step| {
+        if result.is_ok() {
+            result = writer.write(&TraceEntry::new(index, &step, simulator.heads()));
+        }
+        index += 1;
+        None
+    });
+    result.map(|_| outcome)
+}
+
+/// Reads a trace with the line number of every entry, empty lines are
+/// skipped.
+pub fn read_trace(reader: impl BufRead) -> Result<Vec<(usize, TraceEntry)>, TraceError> {
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line_nr = index + 1;
+        let line = line.map_err(|e| TraceError::new(line_nr, e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry =
+            serde_json::from_str(&line).map_err(|e| TraceError::new(line_nr, e.to_string()))?;
+        entries.push((line_nr, entry));
+    }
+    Ok(entries)
+}
+
+/// Runs `machine` on `input` and checks that every step matches the trace
+/// from `read_trace`, the error has the line of the first entry that doesn't.
+pub fn replay(
+    machine: Machine,
+    input: Vec<String>,
+    trace: &[(usize, TraceEntry)],
+) -> Result<(), TraceError> {
+    let mut executor = MachineExecutor::new(machine, input);
+    for (index, (line_nr, expected)) in trace.iter().enumerate() {
+        let line_nr = *line_nr;
+        let step = match executor.next_step() {
+            Ok(Some(step)) => step,
+            Ok(None) => return Err(TraceError::new(line_nr, "the machine stopped before")),
+            Err(e) => return Err(TraceError::new(line_nr, e.to_string())),
+        };
+        let actual = TraceEntry::new(index, &step, executor.heads());
+        if actual != *expected {
+            return Err(TraceError::new(
+                line_nr,
+                format!("expected {:?}, the machine did {:?}", expected, actual),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Where two traces stop agreeing, with the line and the entry on each side,
+/// `None` on the side of a trace that already ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub step: usize,
+    pub left: Option<(usize, TraceEntry)>,
+    pub right: Option<(usize, TraceEntry)>,
+}
+
+/// Compares two traces from `read_trace`, the line numbers don't have to
+/// match.
+pub fn first_divergence(
+    left: &[(usize, TraceEntry)],
+    right: &[(usize, TraceEntry)],
+) -> Option<Divergence> {
+    (0..left.len().max(right.len())).find_map(|step| {
+        let (l, r) = (left.get(step), right.get(step));
+        (l.map(|(_, entry)| entry) != r.map(|(_, entry)| entry)).then(|| Divergence {
+            step,
+            left: l.cloned(),
+            right: r.cloned(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmsim;
+
+    fn counter(carry: &str) -> Machine {
+        tmsim::from_tmsim(&format!(
+            "init: right\naccept: done\n\
+             right,0\nright,0,>\nright,1\nright,1,>\nright,_\ncarry,_,<\n\
+             carry,1\ncarry,0,<\ncarry,0\nright,1,>\ncarry,_\nright,{},>",
+            carry
+        ))
+        .unwrap()
+    }
+
+    fn trace(machine: Machine) -> Vec<u8> {
+        let limits = Limits {
+            steps: Some(100),
+            ..Limits::default()
+        };
+        let mut writer = TraceWriter::new(Vec::new());
+        let mut executor = MachineExecutor::new(machine, vec![]);
+        let outcome = record(&mut executor, &limits, &mut writer).unwrap();
+        assert_eq!(outcome, RunOutcome::StepLimitExceeded);
+        writer.into_inner()
+    }
+
+    #[test]
+    fn replay_and_diff() {
+        // with a blank line before every entry
+        let written = String::from_utf8(trace(counter("1"))).unwrap();
+        let written = written.replace('\n', "\n\n");
+        let entries = read_trace(format!("\n{}", written).as_bytes()).unwrap();
+        assert_eq!(entries.len(), 100);
+        assert!(replay(counter("1"), vec![], &entries).is_ok());
+
+        // carrying into a new cell writes 0 instead of 1, the first time
+        // that happens is the second step
+        let error = replay(counter("0"), vec![], &entries).unwrap_err();
+        assert_eq!(error.line, 4);
+
+        let other = read_trace(&trace(counter("0"))[..]).unwrap();
+        let divergence = first_divergence(&entries, &other).unwrap();
+        assert_eq!(divergence.step, 1);
+        let (line, left) = divergence.left.unwrap();
+        assert_eq!((line, left.written), (4, vec!["1".to_string()]));
+        let (line, right) = divergence.right.unwrap();
+        assert_eq!((line, right.written), (2, vec!["0".to_string()]));
+        assert_eq!(
+            first_divergence(&entries, &entries[..50]).unwrap().right,
+            None
+        );
+    }
+}
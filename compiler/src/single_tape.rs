@@ -0,0 +1,761 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::tm::{Direction, Machine, State, TapeMode, TransitionFunction};
+
+/// One cell of the single band: the symbol of every simulated band together
+/// with a marker for each head that is on this cell. `first` marks the
+/// leftmost cell so the sweeps know where to turn around.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Cell {
+    first: bool,
+    tracks: Vec<(String, bool)>,
+}
+
+impl Cell {
+    fn blank(size: usize) -> Self {
+        Self {
+            first: false,
+            tracks: vec![("_".to_string(), false); size],
+        }
+    }
+
+    fn name(&self) -> String {
+        if *self == Self::blank(self.tracks.len()) {
+            return "_".to_string();
+        }
+        let tracks = self
+            .tracks
+            .iter()
+            .map(|(symbol, head)| format!("{}{}", symbol, if *head { "^" } else { "" }))
+            .collect::<Vec<_>>()
+            .join("|");
+        format!("[{}{}]", if self.first { ">" } else { "" }, tracks)
+    }
+
+    fn heads(&self) -> impl Iterator<Item = usize> + '_ {
+        self.tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, track)| track.1)
+            .map(|(index, _)| index)
+    }
+
+    fn with_heads(&self, heads: &[bool]) -> Self {
+        let mut cell = self.clone();
+        for (track, head) in cell.tracks.iter_mut().zip(heads) {
+            track.1 |= head;
+        }
+        cell
+    }
+}
+
+/// The phases of simulating one step of the original machine, see
+/// `to_single_tape`. `transition` is the index into the transition functions
+/// of `state`, the `bool` vectors hold one entry per band.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Phase {
+    Init,
+    Convert,
+    Rewind,
+    Scan {
+        state: String,
+        seen: Vec<Option<String>>,
+    },
+    Update {
+        state: String,
+        transition: usize,
+        processed: Vec<bool>,
+    },
+    PlaceRight {
+        state: String,
+        transition: usize,
+        processed: Vec<bool>,
+        right: Vec<bool>,
+        left: Vec<bool>,
+    },
+    Return {
+        state: String,
+        transition: usize,
+        processed: Vec<bool>,
+        left: Vec<bool>,
+    },
+    PlaceLeft {
+        state: String,
+        transition: usize,
+        processed: Vec<bool>,
+        left: Vec<bool>,
+    },
+    Extend {
+        state: String,
+        transition: usize,
+        processed: Vec<bool>,
+        left: Vec<bool>,
+    },
+    End(String),
+}
+
+fn bits(bits: &[bool]) -> String {
+    bits.iter().map(|b| if *b { '1' } else { '0' }).collect()
+}
+
+impl Phase {
+    fn name(&self) -> String {
+        match self {
+            Phase::Init => "init".to_string(),
+            Phase::Convert => "convert".to_string(),
+            Phase::Rewind => "rewind".to_string(),
+            Phase::Scan { state, seen } => format!(
+                "scan {} [{}]",
+                state,
+                seen.iter()
+                    .map(|s| s.as_deref().unwrap_or("?"))
+                    .collect::<Vec<_>>()
+                    .join("|")
+            ),
+            Phase::Update {
+                state,
+                transition,
+                processed,
+            } => format!("update {} #{} {}", state, transition, bits(processed)),
+            Phase::PlaceRight {
+                state,
+                transition,
+                processed,
+                right,
+                left,
+            } => format!(
+                "right {} #{} {} {} {}",
+                state,
+                transition,
+                bits(processed),
+                bits(right),
+                bits(left)
+            ),
+            Phase::Return {
+                state,
+                transition,
+                processed,
+                left,
+            } => format!(
+                "return {} #{} {} {}",
+                state,
+                transition,
+                bits(processed),
+                bits(left)
+            ),
+            Phase::PlaceLeft {
+                state,
+                transition,
+                processed,
+                left,
+            } => format!(
+                "left {} #{} {} {}",
+                state,
+                transition,
+                bits(processed),
+                bits(left)
+            ),
+            Phase::Extend {
+                state,
+                transition,
+                processed,
+                left,
+            } => format!(
+                "extend {} #{} {} {}",
+                state,
+                transition,
+                bits(processed),
+                bits(left)
+            ),
+            Phase::End(state) => format!("end {}", state),
+        }
+    }
+
+    /// The state of the original machine this phase belongs to.
+    fn original<'a>(&'a self, machine: &'a Machine) -> &'a str {
+        match self {
+            Phase::Init | Phase::Convert | Phase::Rewind => &machine.start_state_name,
+            Phase::Scan { state, .. }
+            | Phase::Update { state, .. }
+            | Phase::PlaceRight { state, .. }
+            | Phase::Return { state, .. }
+            | Phase::PlaceLeft { state, .. }
+            | Phase::Extend { state, .. }
+            | Phase::End(state) => state,
+        }
+    }
+}
+
+/// Decodes tapes and states of a machine built by `to_single_tape` back into
+/// the bands and states of the original machine.
+#[derive(Debug, Clone)]
+pub struct SingleTapeMapping {
+    pub size: usize,
+    cells: HashMap<String, Cell>,
+    states: HashMap<String, String>,
+}
+
+impl SingleTapeMapping {
+    fn cell(&self, symbol: &str) -> Cell {
+        match self.cells.get(symbol) {
+            Some(cell) => cell.clone(),
+            // input that hasn't been converted yet belongs to the first band
+            None => {
+                let mut cell = Cell::blank(self.size);
+                cell.tracks[0].0 = symbol.to_string();
+                cell
+            }
+        }
+    }
+
+    /// Splits the single band into one band per track. Every band has the
+    /// length of the single band, including its trailing blanks.
+    pub fn decode(&self, tape: &[String]) -> Vec<Vec<String>> {
+        let mut bands = vec![Vec::with_capacity(tape.len()); self.size];
+        for symbol in tape {
+            for (band, track) in bands.iter_mut().zip(self.cell(symbol).tracks) {
+                band.push(track.0);
+            }
+        }
+        bands
+    }
+
+    /// Head positions of the simulated bands, `None` for heads that haven't
+    /// been placed yet because the input is still being converted.
+    pub fn heads(&self, tape: &[String]) -> Vec<Option<usize>> {
+        let mut heads = vec![None; self.size];
+        for (position, symbol) in tape.iter().enumerate() {
+            for head in self.cell(symbol).heads() {
+                heads[head].get_or_insert(position);
+            }
+        }
+        heads
+    }
+
+    /// The state of the original machine a state of the single band machine
+    /// belongs to.
+    pub fn original_state(&self, name: &str) -> Option<&str> {
+        self.states.get(name).map(String::as_str)
+    }
+}
+
+fn all_cells(alphabet: &[String], size: usize) -> Vec<Cell> {
+    let mut tracks = vec![vec![]];
+    for _ in 0..size {
+        tracks = tracks
+            .into_iter()
+            .flat_map(|prefix: Vec<(String, bool)>| {
+                alphabet.iter().flat_map(move |symbol| {
+                    let prefix = prefix.clone();
+                    [false, true].into_iter().map(move |head| {
+                        let mut tracks = prefix.clone();
+                        tracks.push((symbol.clone(), head));
+                        tracks
+                    })
+                })
+            })
+            .collect();
+    }
+    tracks
+        .into_iter()
+        .flat_map(|tracks| {
+            [false, true].into_iter().map(move |first| Cell {
+                first,
+                tracks: tracks.clone(),
+            })
+        })
+        .collect()
+}
+
+/**
+ * Builds a single band machine that simulates a machine with any number of bands
+ *
+ * the band holds one track per simulated band, every cell is a composite symbol
+ * like "[>0^|_]": the symbol of each track, '^' where a head is and '>' on the
+ * leftmost cell. the all blank cell without heads stays '_' so the band grows
+ * like any other band. composites are only used after the input is converted,
+ * so the machine accepts the same input as the original machine.
+ *
+ * each step of the original machine is simulated by
+ * - scanning right from the leftmost cell until every head has been seen
+ * - sweeping back left, writing the symbols and moving the head markers
+ *   of the matching transition function
+ *
+ * a head moving left from the leftmost cell stays where it is on a clamped
+ * band and moves the head of the single band off a left bounded band, with the
+ * symbols of the step partly written. on two-way infinite bands the single
+ * band grows a new leftmost cell instead, so all tracks grow left together and
+ * a band can start with blanks its original band doesn't have.
+ */
+pub fn to_single_tape(machine: &Machine) -> Result<(Machine, SingleTapeMapping), &'static str> {
+    let machine = &machine.expand_wildcards();
+    let size = machine.size;
+    let mut alphabet = machine.alphabet.iter().cloned().collect::<Vec<_>>();
+    alphabet.sort();
+
+    let cells = all_cells(&alphabet, size)
+        .into_iter()
+        .map(|cell| (cell.name(), cell))
+        .collect::<HashMap<_, _>>();
+    if alphabet.iter().any(|s| s != "_" && cells.contains_key(s)) {
+        return Err("alphabet symbol collides with a composite symbol");
+    }
+
+    let mut phases = HashMap::new();
+    let mut queue = VecDeque::from([Phase::Init]);
+    while let Some(phase) = queue.pop_front() {
+        let name = phase.name();
+        if phases.contains_key(&name) {
+            continue;
+        }
+
+        let mut transitions: Vec<(String, Cell, Direction, Phase)> = Vec::new();
+        match &phase {
+            Phase::Init => {
+                for symbol in alphabet.iter() {
+                    let mut cell = Cell::blank(size);
+                    cell.first = true;
+                    cell.tracks.iter_mut().for_each(|track| track.1 = true);
+                    cell.tracks[0].0 = symbol.clone();
+                    transitions.push((symbol.clone(), cell, Direction::Right, Phase::Convert));
+                }
+            }
+            Phase::Convert => {
+                for symbol in alphabet.iter() {
+                    let mut cell = Cell::blank(size);
+                    if symbol == "_" {
+                        transitions.push((symbol.clone(), cell, Direction::Left, Phase::Rewind));
+                    } else {
+                        cell.tracks[0].0 = symbol.clone();
+                        transitions.push((symbol.clone(), cell, Direction::Right, Phase::Convert));
+                    }
+                }
+            }
+            _ => {
+                for (symbol, cell) in cells.iter() {
+                    if let Some((cell, direction, next)) = step(machine, &phase, cell) {
+                        transitions.push((symbol.clone(), cell, direction, next));
+                    }
+                }
+            }
+        }
+
+        let transition_functions = transitions
+            .into_iter()
+            .map(|(symbol, cell, direction, next)| {
+                let function = TransitionFunction::new(
+                    name.clone(),
+                    vec![symbol],
+                    vec![(cell.name(), direction)],
+                    next.name(),
+                );
+                queue.push_back(next);
+                function
+            })
+            .collect();
+        let is_end_state = matches!(phase, Phase::End(_));
+        phases.insert(
+            name,
+            (phase, State::new(transition_functions, is_end_state)),
+        );
+    }
+
+    let states = phases
+        .iter()
+        .map(|(name, (phase, _))| (name.clone(), phase.original(machine).to_string()))
+        .collect::<HashMap<_, _>>();
+    let single = Machine::new(
+        alphabet
+            .into_iter()
+            .chain(cells.keys().cloned())
+            .collect::<HashSet<_>>(),
+        1,
+        phases
+            .into_iter()
+            .map(|(name, (_, state))| (name, state))
+            .collect(),
+        Phase::Init.name(),
+    )?
+    .with_tape_mode(machine.tape_mode);
+
+    Ok((
+        single,
+        SingleTapeMapping {
+            size,
+            cells,
+            states,
+        },
+    ))
+}
+
+/// The phase that simulates `state`, which is scanned unless it's an end state.
+fn start(machine: &Machine, state: &str) -> Phase {
+    if machine.states[state].is_end_state {
+        Phase::End(state.to_string())
+    } else {
+        Phase::Scan {
+            state: state.to_string(),
+            seen: vec![None; machine.size],
+        }
+    }
+}
+
+/// The cell to write, the head movement and the next phase after reading
+/// `cell` in `phase`, `None` if the simulation is stuck.
+fn step(machine: &Machine, phase: &Phase, cell: &Cell) -> Option<(Cell, Direction, Phase)> {
+    match phase.clone() {
+        Phase::Init | Phase::Convert | Phase::End(_) => None,
+        Phase::Rewind => Some(if cell.first {
+            (
+                cell.clone(),
+                Direction::Unchanged,
+                start(machine, &machine.start_state_name),
+            )
+        } else {
+            (cell.clone(), Direction::Left, Phase::Rewind)
+        }),
+        Phase::Scan { state, mut seen } => {
+            for head in cell.heads() {
+                seen[head].get_or_insert_with(|| cell.tracks[head].0.clone());
+            }
+            let read = seen.iter().cloned().collect::<Option<Vec<_>>>();
+            match read {
+                None => Some((cell.clone(), Direction::Right, Phase::Scan { state, seen })),
+                Some(read) => {
+                    let transition = machine.states[&state]
+                        .transition_functions
+                        .iter()
+                        .position(|f| f.bands_requirements == read)?;
+                    Some((
+                        cell.clone(),
+                        Direction::Unchanged,
+                        Phase::Update {
+                            state,
+                            transition,
+                            processed: vec![false; cell.tracks.len()],
+                        },
+                    ))
+                }
+            }
+        }
+        Phase::Update {
+            state,
+            transition,
+            mut processed,
+        } => {
+            let heads = cell
+                .heads()
+                .filter(|head| !processed[*head])
+                .collect::<Vec<_>>();
+            if heads.is_empty() {
+                return Some(if cell.first {
+                    let f = &machine.states[&state].transition_functions[transition];
+                    (
+                        cell.clone(),
+                        Direction::Unchanged,
+                        start(machine, &f.next_state_name),
+                    )
+                } else {
+                    (
+                        cell.clone(),
+                        Direction::Left,
+                        Phase::Update {
+                            state,
+                            transition,
+                            processed,
+                        },
+                    )
+                });
+            }
+
+            let actions = &machine.states[&state].transition_functions[transition].bands_actions;
+            if cell.first
+                && machine.tape_mode == TapeMode::TwoWayInfinite
+                && heads.iter().any(|head| actions[*head].1 == Direction::Left)
+            {
+                // the heads moving left go first, onto a new leftmost cell
+                let mut written = cell.clone();
+                written.first = false;
+                let mut left = vec![false; processed.len()];
+                for head in heads {
+                    let (symbol, direction) = &actions[head];
+                    if *direction == Direction::Left {
+                        processed[head] = true;
+                        written.tracks[head] = (symbol.clone(), false);
+                        left[head] = true;
+                    }
+                }
+                return Some((
+                    written,
+                    Direction::Left,
+                    Phase::Extend {
+                        state,
+                        transition,
+                        processed,
+                        left,
+                    },
+                ));
+            }
+            if cell.first
+                && machine.tape_mode == TapeMode::LeftBounded
+                && heads.iter().any(|head| actions[*head].1 == Direction::Left)
+            {
+                // the simulated head falls off, so does the head of this band
+                return Some((
+                    cell.clone(),
+                    Direction::Left,
+                    Phase::Update {
+                        state,
+                        transition,
+                        processed,
+                    },
+                ));
+            }
+
+            let mut written = cell.clone();
+            let mut right = vec![false; processed.len()];
+            let mut left = vec![false; processed.len()];
+            for head in heads {
+                let (symbol, direction) = &actions[head];
+                processed[head] = true;
+                written.tracks[head].0 = symbol.clone();
+                match direction {
+                    Direction::Right => right[head] = true,
+                    Direction::Left if !cell.first => left[head] = true,
+                    _ => continue,
+                }
+                written.tracks[head].1 = false;
+            }
+            Some(if right.contains(&true) {
+                (
+                    written,
+                    Direction::Right,
+                    Phase::PlaceRight {
+                        state,
+                        transition,
+                        processed,
+                        right,
+                        left,
+                    },
+                )
+            } else if left.contains(&true) {
+                (
+                    written,
+                    Direction::Left,
+                    Phase::PlaceLeft {
+                        state,
+                        transition,
+                        processed,
+                        left,
+                    },
+                )
+            } else {
+                (
+                    written,
+                    Direction::Unchanged,
+                    Phase::Update {
+                        state,
+                        transition,
+                        processed,
+                    },
+                )
+            })
+        }
+        Phase::PlaceRight {
+            state,
+            transition,
+            processed,
+            right,
+            left,
+        } => Some((
+            cell.with_heads(&right),
+            Direction::Left,
+            if left.contains(&true) {
+                Phase::Return {
+                    state,
+                    transition,
+                    processed,
+                    left,
+                }
+            } else {
+                Phase::Update {
+                    state,
+                    transition,
+                    processed,
+                }
+            },
+        )),
+        Phase::Return {
+            state,
+            transition,
+            processed,
+            left,
+        } => Some((
+            cell.clone(),
+            Direction::Left,
+            Phase::PlaceLeft {
+                state,
+                transition,
+                processed,
+                left,
+            },
+        )),
+        Phase::PlaceLeft {
+            state,
+            transition,
+            processed,
+            left,
+        } => Some((
+            cell.with_heads(&left),
+            Direction::Unchanged,
+            Phase::Update {
+                state,
+                transition,
+                processed,
+            },
+        )),
+        Phase::Extend {
+            state,
+            transition,
+            processed,
+            left,
+        } => {
+            if *cell != Cell::blank(cell.tracks.len()) {
+                return None;
+            }
+            let mut first = cell.with_heads(&left);
+            first.first = true;
+            Some((
+                first,
+                Direction::Right,
+                Phase::Update {
+                    state,
+                    transition,
+                    processed,
+                },
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::sim::{Limits, MachineExecutor, RunOutcome, Simulator};
+    use crate::test_util::random_machine;
+
+    const SYMBOLS: [&str; 2] = ["_", "1"];
+
+    fn trimmed(band: &[String]) -> Vec<String> {
+        let end = band.iter().rposition(|s| s != "_").map_or(0, |i| i + 1);
+        band[..end].to_vec()
+    }
+
+    /// The symbols that aren't blank by their distance from the head.
+    fn around(band: &[String], head: usize) -> Vec<(isize, String)> {
+        band.iter()
+            .enumerate()
+            .filter(|(_, symbol)| *symbol != "_")
+            .map(|(position, symbol)| (position as isize - head as isize, symbol.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn simulates_the_original_machine() {
+        let mut random = Random::new(0x9e37_79b9_7f4a_7c15);
+        let mut compared = 0;
+        for mode in [
+            TapeMode::Clamped,
+            TapeMode::LeftBounded,
+            TapeMode::TwoWayInfinite,
+        ] {
+            for _ in 0..60 {
+                let machine = random_machine(&mut random, &SYMBOLS, 2, mode);
+                let (single, mapping) = to_single_tape(&machine).unwrap();
+                for _ in 0..5 {
+                    let input = (0..random.below(6))
+                        .map(|_| SYMBOLS[1].to_string())
+                        .collect::<Vec<_>>();
+                    let mut original = MachineExecutor::new(machine.clone(), input.clone());
+                    let outcome = original.run(&Limits {
+                        steps: Some(50),
+                        ..Limits::default()
+                    });
+                    if outcome == RunOutcome::StepLimitExceeded {
+                        continue;
+                    }
+                    let mut simulation = MachineExecutor::new(single.clone(), input);
+                    let simulated = simulation.run(&Limits {
+                        steps: Some(1_000_000),
+                        ..Limits::default()
+                    });
+                    // the single band machine has one band to fall off
+                    match &outcome {
+                        RunOutcome::Rejected { .. } => {
+                            assert!(matches!(simulated, RunOutcome::Rejected { .. }))
+                        }
+                        RunOutcome::FellOffTape { .. } => {
+                            assert_eq!(simulated, RunOutcome::FellOffTape { band: 0 })
+                        }
+                        outcome => assert_eq!(&simulated, outcome),
+                    }
+
+                    // falling off leaves the single band in the middle of a step
+                    if matches!(outcome, RunOutcome::FellOffTape { .. }) {
+                        compared += 1;
+                        continue;
+                    }
+                    let tape = simulation.band_symbols().remove(0);
+                    let bands = mapping.decode(&tape);
+                    let heads = mapping.heads(&tape);
+                    if mode == TapeMode::TwoWayInfinite {
+                        // the tracks grow left together, the bands each on their own
+                        for ((band, head), original) in
+                            bands.iter().zip(heads).zip(original.bands())
+                        {
+                            assert_eq!(
+                                around(band, head.unwrap()),
+                                around(
+                                    &original.symbols(),
+                                    (original.head() - original.offset()) as usize
+                                )
+                            );
+                        }
+                    } else {
+                        let original_heads = original.heads().into_iter().map(|h| h as usize);
+                        for (band, original) in bands.iter().zip(original.band_symbols()) {
+                            assert_eq!(trimmed(band), trimmed(&original));
+                        }
+                        assert_eq!(heads, original_heads.map(Some).collect::<Vec<_>>());
+                    }
+                    compared += 1;
+                }
+            }
+        }
+        assert!(compared > 600, "only {} runs halted", compared);
+    }
+
+    #[test]
+    fn rejects_unsupported_machines() {
+        // the name of the composite of a blank with a head and a blank
+        let symbol = "[_^|_]".to_string();
+        let states = HashMap::from([(
+            "q0".to_string(),
+            State::new(
+                vec![TransitionFunction::new(
+                    "q0".to_string(),
+                    vec![symbol.clone(), "_".to_string()],
+                    vec![("_".to_string(), Direction::Right); 2],
+                    "q0".to_string(),
+                )],
+                false,
+            ),
+        )]);
+        let machine = Machine::new(HashSet::from([symbol]), 2, states, "q0".to_string()).unwrap();
+        assert_eq!(
+            to_single_tape(&machine).unwrap_err(),
+            "alphabet symbol collides with a composite symbol"
+        );
+    }
+}
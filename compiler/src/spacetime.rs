@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+
+use crate::sim::{Limits, MachineExecutor, RunOutcome, Simulator};
+
+/// Fill colours of the non blank symbols, in sorted symbol order.
+const SYMBOL_COLORS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+/// Colours of the head marker, in sorted state order.
+const STATE_COLORS: [&str; 6] = [
+    "#000000", "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4",
+];
+/// Background colours of the ANSI output, matching `SYMBOL_COLORS` loosely.
+const SYMBOL_ANSI: [u8; 8] = [44, 43, 42, 41, 45, 46, 47, 100];
+/// Side length of a cell in the SVG.
+const CELL: usize = 8;
+
+struct Row {
+    state: String,
+    offset: isize,
+    cells: Vec<String>,
+    head: isize,
+}
+
+/// The configurations of one band over a run, one row per step.
+pub struct SpaceTime {
+    rows: Vec<Row>,
+    symbols: Vec<String>,
+    states: Vec<String>,
+    /// Leftmost and rightmost cell of all rows.
+    min: isize,
+    max: isize,
+}
+
+impl SpaceTime {
+    /// Runs `executor` and records `band` before the first and after every
+    /// step.
+    pub fn record(
+        executor: &mut MachineExecutor,
+        band: usize,
+        limits: &Limits,
+    ) -> (Self, RunOutcome) {
+        let row = |executor: &MachineExecutor| {
+            let tape = &executor.bands()[band];
+            Row {
+                state: executor.current_state_name().to_string(),
+                offset: tape.offset(),
+                cells: tape.symbols(),
+                head: tape.head(),
+            }
+        };
+        let mut rows = vec![row(executor)];
+        let outcome = executor.run_until(limits, |executor, _| {
+            rows.push(row(executor));
+            None
+        });
+
+        let symbols = rows
+            .iter()
+            .flat_map(|row| row.cells.iter())
+            .filter(|s| *s != "_")
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let states = rows
+            .iter()
+            .map(|row| row.state.clone())
+            .collect::<BTreeSet<_>>();
+        let min = rows.iter().map(|row| row.offset).min().unwrap();
+        let max = rows
+            .iter()
+            .map(|row| row.offset + row.cells.len() as isize - 1)
+            .max()
+            .unwrap();
+        let space_time = Self {
+            rows,
+            symbols: symbols.into_iter().collect(),
+            states: states.into_iter().collect(),
+            min,
+            max,
+        };
+        (space_time, outcome)
+    }
+
+    fn symbol_index(&self, symbol: &str) -> Option<usize> {
+        self.symbols.iter().position(|s| s == symbol)
+    }
+
+    fn state_index(&self, state: &str) -> usize {
+        self.states.iter().position(|s| s == state).unwrap()
+    }
+
+    /// The diagram as SVG: blank cells are white, the head is a dot coloured
+    /// by the state, hovering it shows the state name.
+    pub fn to_svg(&self) -> String {
+        let width = (self.max - self.min + 1) as usize * CELL;
+        let height = self.rows.len() * CELL;
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            width, height, width, height
+        );
+        out.push_str(&format!(
+            "  <rect width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>\n",
+            width, height
+        ));
+        for (y, row) in self.rows.iter().enumerate() {
+            for (index, symbol) in row.cells.iter().enumerate() {
+                let color = match self.symbol_index(symbol) {
+                    Some(i) => SYMBOL_COLORS[i % SYMBOL_COLORS.len()],
+                    None => continue,
+                };
+                let x = (row.offset + index as isize - self.min) as usize;
+                out.push_str(&format!(
+                    "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                    x * CELL,
+                    y * CELL,
+                    CELL,
+                    CELL,
+                    color
+                ));
+            }
+            let x = (row.head - self.min) as usize;
+            out.push_str(&format!(
+                "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"><title>{}</title></circle>\n",
+                x * CELL + CELL / 2,
+                y * CELL + CELL / 2,
+                CELL / 4,
+                STATE_COLORS[self.state_index(&row.state) % STATE_COLORS.len()],
+                escape_xml(&row.state)
+            ));
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    /// The diagram for a terminal, one line per row with the state at the
+    /// end. Cells show the first character of their symbol on a background
+    /// coloured by the symbol, the head cell is inverted.
+    pub fn to_ansi(&self) -> String {
+        let mut out = String::new();
+        for row in self.rows.iter() {
+            for position in self.min..=self.max {
+                let index = position - row.offset;
+                let symbol = if index >= 0 && (index as usize) < row.cells.len() {
+                    row.cells[index as usize].as_str()
+                } else {
+                    "_"
+                };
+                let mut codes = Vec::new();
+                if let Some(i) = self.symbol_index(symbol) {
+                    codes.push(SYMBOL_ANSI[i % SYMBOL_ANSI.len()].to_string());
+                }
+                if position == row.head {
+                    codes.push("7".to_string());
+                }
+                let c = if symbol == "_" {
+                    ' '
+                } else {
+                    symbol.chars().next().unwrap()
+                };
+                if codes.is_empty() {
+                    out.push(c);
+                } else {
+                    out.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), c));
+                }
+            }
+            out.push_str(&format!(" {}\n", row.state));
+        }
+        out
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compact;
+
+    #[test]
+    fn busy_beaver_rows() {
+        let busy_beaver = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        let mut executor = MachineExecutor::new(busy_beaver, vec![]);
+        let (space_time, outcome) = SpaceTime::record(&mut executor, 0, &Limits::default());
+        assert_eq!(outcome, RunOutcome::Accepted);
+
+        let ansi = space_time.to_ansi();
+        let lines = ansi.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].ends_with(" A"));
+        assert!(lines[6].ends_with(" Z"));
+
+        let svg = space_time.to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<circle").count(), 7);
+        // one rect per 1 on the band, the steps reading a 1 don't add one
+        assert_eq!(svg.matches("<rect x").count(), 1 + 2 + 2 + 3 + 4 + 4);
+    }
+}
@@ -0,0 +1,63 @@
+//! Fixtures shared by the tests of the machine transformations.
+
+use std::collections::HashMap;
+
+use crate::random::Random;
+use crate::tm::{Direction, Machine, State, TapeMode, TransitionFunction};
+
+const DIRECTIONS: [Direction; 3] = [Direction::Left, Direction::Right, Direction::Unchanged];
+
+/// A machine with `bands` bands over `symbols`, the states `q0` to `q2` and
+/// the end state `q3`. Most combinations of symbols have a transition
+/// function.
+pub fn random_machine(
+    random: &mut Random,
+    symbols: &[&str],
+    bands: usize,
+    mode: TapeMode,
+) -> Machine {
+    let mut reads = vec![vec![]];
+    for _ in 0..bands {
+        reads = reads
+            .into_iter()
+            .flat_map(|read: Vec<String>| {
+                symbols.iter().map(move |symbol| {
+                    let mut read = read.clone();
+                    read.push(symbol.to_string());
+                    read
+                })
+            })
+            .collect();
+    }
+
+    let mut states = HashMap::new();
+    for state in 0..3 {
+        let origin = format!("q{}", state);
+        let mut functions = Vec::new();
+        for read in reads.iter() {
+            if random.below(5) == 0 {
+                continue;
+            }
+            let actions = (0..bands)
+                .map(|_| {
+                    (
+                        symbols[random.below(symbols.len())].to_string(),
+                        DIRECTIONS[random.below(3)].clone(),
+                    )
+                })
+                .collect();
+            functions.push(TransitionFunction::new(
+                origin.clone(),
+                read.clone(),
+                actions,
+                format!("q{}", random.below(4)),
+            ));
+        }
+        states.insert(origin, State::new(functions, false));
+    }
+    states.insert("q3".to_string(), State::new(vec![], true));
+    let alphabet = symbols.iter().map(|s| s.to_string()).collect();
+    Machine::new(alphabet, bands, states, "q0".to_string())
+        .unwrap()
+        .with_tape_mode(mode)
+}
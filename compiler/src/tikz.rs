@@ -0,0 +1,254 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::dot::{direction_letter, edge_label, parallel_edges};
+use crate::tm::Machine;
+
+/// Number of states placed next to each other before starting a new row.
+const ROW_LENGTH: usize = 4;
+
+/// Escapes a state name or symbol for LaTeX text mode. The blank `_` is
+/// rendered as a visible space.
+pub fn escape(s: &str) -> String {
+    if s == "_" {
+        return "\\textvisiblespace{}".to_string();
+    }
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '\'' => out.push_str("\\textquotesingle{}"),
+            '"' => out.push_str("\\textquotedbl{}"),
+            '_' | '#' | '$' | '%' | '&' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// States in breadth first order from the start state, unreachable states
+/// last in name order. This order drives the layout so the same machine
+/// always produces the same picture.
+fn state_order(machine: &Machine) -> Vec<&str> {
+    let mut order = Vec::new();
+    let mut queue = VecDeque::from([machine.start_state_name.as_str()]);
+    while let Some(name) = queue.pop_front() {
+        if order.contains(&name) {
+            continue;
+        }
+        order.push(name);
+        for f in machine.states[name].transition_functions.iter() {
+            queue.push_back(&f.next_state_name);
+        }
+    }
+    let mut rest = machine
+        .states
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !order.contains(name))
+        .collect::<Vec<_>>();
+    rest.sort_unstable();
+    order.append(&mut rest);
+    order
+}
+
+/// Renders the state diagram as a `tikzpicture` using the `automata` and
+/// `positioning` libraries. States are laid out in rows of `ROW_LENGTH` in
+/// breadth first order.
+pub fn to_tikz(machine: &Machine) -> String {
+    let order = state_order(machine);
+    let ids = order
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (*name, format!("s{}", index)))
+        .collect::<HashMap<_, _>>();
+
+    let mut out = String::from(
+        "\\begin{tikzpicture}[->, >=stealth, auto, node distance=2.5cm, every state/.style={minimum size=1cm}]\n",
+    );
+    for (index, name) in order.iter().enumerate() {
+        let mut options = vec!["state"];
+        if index == 0 {
+            options.push("initial");
+        }
+        if machine.states[*name].is_end_state {
+            options.push("accepting");
+        }
+        let position = if index == 0 {
+            String::new()
+        } else if index % ROW_LENGTH == 0 {
+            format!(", below=of s{}", index - ROW_LENGTH)
+        } else {
+            format!(", right=of s{}", index - 1)
+        };
+        out.push_str(&format!(
+            "    \\node[{}{}] ({}) {{{}}};\n",
+            options.join(", "),
+            position,
+            ids[name],
+            escape(name)
+        ));
+    }
+
+    out.push_str("    \\path\n");
+    for name in order.iter() {
+        for (next, functions) in parallel_edges(machine, name) {
+            let label = edge_label(&functions, escape, "$\\rightarrow$").join(" \\\\ ");
+            let shape = if next == *name {
+                "loop above"
+            } else if machine.states[next]
+                .transition_functions
+                .iter()
+                .any(|f| f.next_state_name == *name)
+            {
+                "bend left"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "        ({}) edge[{}] node[align=center] {{{}}} ({})\n",
+                ids[name], shape, label, ids[next]
+            ));
+        }
+    }
+    out.push_str("    ;\n\\end{tikzpicture}\n");
+    out
+}
+
+/// Renders the transition table as a `tabular` with one row per state and one
+/// column per symbol (per combination of symbols that is read for machines
+/// with more than one band). Each cell lists the written symbols, the head
+/// movements and the next state. Wildcards are expanded into explicit cells.
+pub fn to_tabular(machine: &Machine) -> String {
+    let machine = &machine.expand_wildcards();
+    let order = state_order(machine);
+    let columns = if machine.size == 1 {
+        let mut symbols = machine
+            .alphabet
+            .iter()
+            .filter(|s| *s != "_")
+            .map(|s| vec![s.clone()])
+            .collect::<Vec<_>>();
+        symbols.sort();
+        symbols.push(vec!["_".to_string()]);
+        symbols
+    } else {
+        machine
+            .states
+            .values()
+            .flat_map(|state| state.transition_functions.iter())
+            .map(|f| f.bands_requirements.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    };
+    let symbols = |symbols: &[String]| {
+        symbols
+            .iter()
+            .map(|s| escape(s))
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+
+    let mut out = format!("\\begin{{tabular}}{{l|{}}}\n", "c".repeat(columns.len()));
+    out.push_str(&format!(
+        "    & {} \\\\\n    \\hline\n",
+        columns
+            .iter()
+            .map(|c| format!("\\texttt{{{}}}", symbols(c)))
+            .collect::<Vec<_>>()
+            .join(" & ")
+    ));
+    for name in order {
+        let state = &machine.states[name];
+        let mut label = escape(name);
+        if name == machine.start_state_name {
+            label = format!("$\\rightarrow$ {}", label);
+        }
+        if state.is_end_state {
+            label = format!("{} $\\ast$", label);
+        }
+        let cells = columns.iter().map(|column| {
+            match state
+                .transition_functions
+                .iter()
+                .find(|f| f.bands_requirements == *column)
+            {
+                Some(f) => format!(
+                    "\\texttt{{{}}}, {}, {}",
+                    symbols(
+                        &f.bands_actions
+                            .iter()
+                            .map(|a| a.0.clone())
+                            .collect::<Vec<_>>()
+                    ),
+                    f.bands_actions
+                        .iter()
+                        .map(|a| direction_letter(&a.1))
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                    escape(&f.next_state_name)
+                ),
+                None => "--".to_string(),
+            }
+        });
+        out.push_str(&format!(
+            "    {} & {} \\\\\n",
+            label,
+            cells.collect::<Vec<_>>().join(" & ")
+        ));
+    }
+    out.push_str("\\end{tabular}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compact, tmsim};
+
+    const MARK: &str = "init: q0\naccept: done\n\
+        q0,1\nq1,1',>\nq1,1\nq1,1,>\nq1,_\ndone,_,-";
+
+    #[test]
+    fn escapes_symbols() {
+        assert_eq!(escape("1'"), "1\\textquotesingle{}");
+        assert_eq!(escape("_"), "\\textvisiblespace{}");
+        assert_eq!(escape("a_b"), "a\\_b");
+    }
+
+    #[test]
+    fn tabular() {
+        let machine = tmsim::from_tmsim(MARK).unwrap();
+        let expected = r"\begin{tabular}{l|ccc}
+    & \texttt{1} & \texttt{1\textquotesingle{}} & \texttt{\textvisiblespace{}} \\
+    \hline
+    $\rightarrow$ q0 & \texttt{1\textquotesingle{}}, R, q1 & -- & -- \\
+    q1 & \texttt{1}, R, q1 & -- & \texttt{\textvisiblespace{}}, N, done \\
+    done $\ast$ & -- & -- & -- \\
+\end{tabular}
+";
+        assert_eq!(to_tabular(&machine), expected);
+    }
+
+    #[test]
+    fn deterministic_layout() {
+        // every import builds its maps with a new random order
+        let first = to_tikz(&tmsim::from_tmsim(MARK).unwrap());
+        for _ in 0..10 {
+            assert_eq!(to_tikz(&tmsim::from_tmsim(MARK).unwrap()), first);
+        }
+
+        // more states than fit in a row
+        let bb5 = "1RB1LC_1RC1RB_1RD0LE_1LA1LD_1RZ0LA";
+        let first = to_tikz(&compact::from_compact(bb5).unwrap());
+        assert!(first.contains("below=of s0"));
+        for _ in 0..10 {
+            assert_eq!(to_tikz(&compact::from_compact(bb5).unwrap()), first);
+        }
+    }
+}
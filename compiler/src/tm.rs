@@ -3,13 +3,34 @@ use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// As a band requirement it matches any symbol, as the symbol of a band
+/// action it leaves the cell as it is.
+pub const WILDCARD: &str = "*";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     Right,
     Left,
     Unchanged,
 }
 
+/// What happens when a head moves left from the cell the input starts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TapeMode {
+    /// The band grows to the left, head positions become negative.
+    TwoWayInfinite,
+    /// Moving left from cell 0 is an error.
+    LeftBounded,
+    /// The head stays on cell 0.
+    Clamped,
+}
+
+impl Default for TapeMode {
+    fn default() -> Self {
+        TapeMode::Clamped
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransitionFunction {
     pub origin: String,
@@ -36,6 +57,23 @@ impl TransitionFunction {
             next_state_name,
         }
     }
+
+    /// Whether the function applies to the symbols under the heads, every
+    /// band has to match its requirement.
+    pub fn matches<S: AsRef<str>>(&self, symbols: &[S]) -> bool {
+        self.bands_requirements
+            .iter()
+            .zip(symbols)
+            .all(|(requirement, symbol)| requirement == WILDCARD || requirement == symbol.as_ref())
+    }
+
+    /// The symbol written on `band` when `read` is under its head.
+    pub fn written<'a>(&'a self, band: usize, read: &'a str) -> &'a str {
+        match self.bands_actions[band].0.as_str() {
+            WILDCARD => read,
+            symbol => symbol,
+        }
+    }
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
@@ -57,6 +95,8 @@ pub struct Machine {
     pub size: usize,
     pub states: HashMap<String, State>,
     pub start_state_name: String,
+    #[serde(default)]
+    pub tape_mode: TapeMode,
 }
 impl Machine {
     pub fn new(
@@ -71,6 +111,10 @@ impl Machine {
         //TODO make sure the machine has a way to end
         let mut alphabet = alphabet;
         alphabet.insert("_".to_string());
+        if alphabet.contains(WILDCARD) {
+            return Err("the wildcard can't be part of the alphabet");
+        }
+        let known = |symbol: &String| symbol == WILDCARD || alphabet.contains(symbol);
         if !states.contains_key(&start_state) {
             return Err("origin not in states");
         }
@@ -82,7 +126,7 @@ impl Machine {
                 );
                 }
                 for requirement in f.bands_requirements.iter() {
-                    if !alphabet.contains(requirement) {
+                    if !known(requirement) {
                         return Err("character not defined in alphabet");
                     }
                 }
@@ -95,7 +139,7 @@ impl Machine {
                     return Err("next state not found");
                 }
                 for action in f.bands_actions.iter() {
-                    if !alphabet.contains(&action.0) {
+                    if !known(&action.0) {
                         return Err("character not defined in alphabet");
                     }
                 }
@@ -107,8 +151,70 @@ impl Machine {
             size,
             states,
             start_state_name: start_state,
+            tape_mode: TapeMode::default(),
         })
     }
+
+    pub fn with_tape_mode(mut self, tape_mode: TapeMode) -> Self {
+        self.tape_mode = tape_mode;
+        self
+    }
+
+    /// An equivalent machine without wildcards, every function with wildcards
+    /// is replaced by one function per symbol combination it matches that
+    /// isn't already taken by an earlier function of the same state.
+    pub fn expand_wildcards(&self) -> Machine {
+        let mut symbols = self.alphabet.iter().cloned().collect::<Vec<_>>();
+        symbols.sort();
+
+        let mut machine = self.clone();
+        for state in machine.states.values_mut() {
+            let functions = std::mem::take(&mut state.transition_functions);
+            for (index, f) in functions.iter().enumerate() {
+                let mut combinations = vec![vec![]];
+                for requirement in f.bands_requirements.iter() {
+                    let options = if requirement == WILDCARD {
+                        &symbols[..]
+                    } else {
+                        std::slice::from_ref(requirement)
+                    };
+                    combinations = combinations
+                        .into_iter()
+                        .flat_map(|read: Vec<String>| {
+                            options.iter().map(move |symbol| {
+                                let mut read = read.clone();
+                                read.push(symbol.clone());
+                                read
+                            })
+                        })
+                        .collect();
+                }
+                for read in combinations {
+                    if functions[..index]
+                        .iter()
+                        .any(|earlier| earlier.matches(&read))
+                    {
+                        continue;
+                    }
+                    let actions = f
+                        .bands_actions
+                        .iter()
+                        .enumerate()
+                        .map(|(band, (_, direction))| {
+                            (f.written(band, &read[band]).to_string(), direction.clone())
+                        })
+                        .collect();
+                    state.transition_functions.push(TransitionFunction::new(
+                        f.origin.clone(),
+                        read,
+                        actions,
+                        f.next_state_name.clone(),
+                    ));
+                }
+            }
+        }
+        machine
+    }
 }
 
 /**
@@ -0,0 +1,321 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::tm::{Direction, Machine, State, TapeMode, TransitionFunction, WILDCARD};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ImportError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/**
+ * Import and export of the text syntax used by turingmachinesimulator.com
+ *
+ * name: copy
+ * init: q0
+ * accept: q1
+ *
+ * //current state, read symbol for each band
+ * q0,0
+ * //next state, written symbol for each band, direction for each band
+ * q0,1,>
+ *
+ * q0,_
+ * q1,_,-
+ *
+ * multi tape machines list one symbol and one direction per band:
+ * q0,0,_
+ * q0,0,0,>,>
+ *
+ * '*' reads any symbol and writes the symbol that is already there
+ *
+ * bands are two-way infinite like on the website
+ */
+pub fn from_tmsim(src: &str) -> Result<Machine, ImportError> {
+    let mut init = None;
+    let mut accept = Vec::new();
+    let mut size = None;
+    let mut alphabet = HashSet::new();
+    let mut states: HashMap<String, State> = HashMap::new();
+    let mut pending_read: Option<(usize, Vec<&str>)> = None;
+
+    for (index, raw) in src.lines().enumerate() {
+        let line_nr = index + 1;
+        let line = raw.split("//").next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if pending_read.is_none() {
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                match key.trim() {
+                    "name" => {}
+                    "init" => init = Some((line_nr, value.to_string())),
+                    "accept" => accept.extend(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|s| !s.is_empty())
+                            .map(str::to_string),
+                    ),
+                    other => {
+                        return Err(ImportError::new(
+                            line_nr,
+                            format!("unsupported directive `{}`", other),
+                        ))
+                    }
+                }
+                continue;
+            }
+        }
+
+        let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+        if fields.iter().any(|f| f.is_empty()) {
+            return Err(ImportError::new(line_nr, "empty field"));
+        }
+
+        let (read_line, read) = match pending_read.take() {
+            None => {
+                if fields.len() < 2 {
+                    return Err(ImportError::new(
+                        line_nr,
+                        "expected a state followed by the symbols to read",
+                    ));
+                }
+                let bands = fields.len() - 1;
+                match size {
+                    None => size = Some(bands),
+                    Some(size) if size != bands => {
+                        return Err(ImportError::new(
+                            line_nr,
+                            format!("expected {} bands, found {}", size, bands),
+                        ))
+                    }
+                    _ => {}
+                }
+                pending_read = Some((line_nr, fields));
+                continue;
+            }
+            Some(read) => read,
+        };
+
+        let bands = read.len() - 1;
+        if fields.len() != 1 + 2 * bands {
+            return Err(ImportError::new(
+                line_nr,
+                format!(
+                    "expected the next state, {} symbols and {} directions",
+                    bands, bands
+                ),
+            ));
+        }
+        let directions = fields[1 + bands..]
+            .iter()
+            .map(|d| match *d {
+                "<" => Ok(Direction::Left),
+                ">" => Ok(Direction::Right),
+                "-" => Ok(Direction::Unchanged),
+                other => Err(ImportError::new(
+                    line_nr,
+                    format!("unsupported direction `{}`", other),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let origin = read[0].to_string();
+        let requirements = read[1..].iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let actions = fields[1..=bands]
+            .iter()
+            .map(|s| s.to_string())
+            .zip(directions)
+            .collect::<Vec<_>>();
+        let next_state_name = fields[0].to_string();
+
+        alphabet.extend(
+            requirements
+                .iter()
+                .chain(actions.iter().map(|a| &a.0))
+                .filter(|s| *s != WILDCARD)
+                .cloned(),
+        );
+        states
+            .entry(next_state_name.clone())
+            .or_insert_with(|| State::new(vec![], false));
+        let state = states
+            .entry(origin.clone())
+            .or_insert_with(|| State::new(vec![], false));
+        if state
+            .transition_functions
+            .iter()
+            .any(|f| f.bands_requirements == requirements)
+        {
+            return Err(ImportError::new(
+                read_line,
+                format!("duplicate transition for state `{}`", origin),
+            ));
+        }
+        state.transition_functions.push(TransitionFunction::new(
+            origin,
+            requirements,
+            actions,
+            next_state_name,
+        ));
+    }
+
+    if let Some((line_nr, _)) = pending_read {
+        return Err(ImportError::new(
+            line_nr,
+            "transition is missing its action line",
+        ));
+    }
+    let (init_line, start_state) = init.ok_or_else(|| ImportError::new(1, "missing `init:`"))?;
+    states
+        .entry(start_state.clone())
+        .or_insert_with(|| State::new(vec![], false));
+    for name in accept {
+        states
+            .entry(name)
+            .or_insert_with(|| State::new(vec![], false))
+            .is_end_state = true;
+    }
+
+    Machine::new(alphabet, size.unwrap_or(1), states, start_state)
+        .map(|machine| machine.with_tape_mode(TapeMode::TwoWayInfinite))
+        .map_err(|e| ImportError::new(init_line, e))
+}
+
+pub fn to_tmsim(machine: &Machine, name: &str) -> Result<String, &'static str> {
+    let valid = |s: &str| !s.is_empty() && !s.contains(|c: char| c == ',' || c.is_whitespace());
+    let mut names = machine.states.keys().collect::<Vec<_>>();
+    names.sort_by_key(|n| (*n != &machine.start_state_name, *n));
+
+    let accept = names
+        .iter()
+        .filter(|n| machine.states[**n].is_end_state)
+        .map(|n| n.as_str())
+        .collect::<Vec<_>>();
+    let mut out = format!(
+        "name: {}\ninit: {}\naccept: {}\n",
+        name,
+        machine.start_state_name,
+        accept.join(", ")
+    );
+
+    for name in names {
+        if !valid(name) {
+            return Err("state name can't be represented in this format");
+        }
+        for f in machine.states[name].transition_functions.iter() {
+            if f.bands_requirements
+                .iter()
+                .chain(f.bands_actions.iter().map(|a| &a.0))
+                .any(|s| !valid(s))
+            {
+                return Err("symbol can't be represented in this format");
+            }
+            let directions = f.bands_actions.iter().map(|a| match a.1 {
+                Direction::Left => "<",
+                Direction::Right => ">",
+                Direction::Unchanged => "-",
+            });
+            out.push_str(&format!(
+                "\n{},{}\n{},{},{}\n",
+                name,
+                f.bands_requirements.join(","),
+                f.next_state_name,
+                f.bands_actions
+                    .iter()
+                    .map(|a| a.0.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                directions.collect::<Vec<_>>().join(",")
+            ));
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINARY_INCREMENT: &str = "name: increment
+init: right
+accept: done
+
+// walk to the end of the number
+right,0
+right,0,>
+
+right,1
+right,1,>
+
+right,_
+carry,_,<
+
+carry,1
+carry,0,<
+
+carry,0
+done,1,-
+
+carry,_
+done,1,-
+";
+
+    #[test]
+    fn import_single_band() {
+        let machine = from_tmsim(BINARY_INCREMENT).unwrap();
+        assert_eq!(machine.size, 1);
+        assert_eq!(machine.start_state_name, "right");
+        assert!(machine.states["done"].is_end_state);
+        assert_eq!(machine.states["carry"].transition_functions.len(), 3);
+    }
+
+    #[test]
+    fn round_trip() {
+        let machine = from_tmsim(BINARY_INCREMENT).unwrap();
+        let exported = to_tmsim(&machine, "increment").unwrap();
+        let reimported = from_tmsim(&exported).unwrap();
+        assert_eq!(to_tmsim(&reimported, "increment").unwrap(), exported);
+    }
+
+    #[test]
+    fn import_multi_band() {
+        let machine =
+            from_tmsim("init: q0\naccept: q1\nq0,0,_\nq0,0,0,>,>\nq0,_,_\nq1,_,_,-,-").unwrap();
+        assert_eq!(machine.size, 2);
+        let f = &machine.states["q0"].transition_functions[0];
+        assert_eq!(f.bands_requirements, vec!["0", "_"]);
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let err = from_tmsim("init: q0\n\nq0,0\nq1,1,^").unwrap_err();
+        assert_eq!(err.line, 4);
+        let err = from_tmsim("init: q0\nhalt: q1").unwrap_err();
+        assert_eq!(err.line, 2);
+        let err = from_tmsim("init: q0\nq0,0\nq1,1,>\nq0,0,1\nq1,1,1,>,>").unwrap_err();
+        assert_eq!(err.line, 4);
+    }
+}
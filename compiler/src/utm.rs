@@ -0,0 +1,612 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::tm::{Direction, Machine, State, TapeMode, TransitionFunction};
+
+/// Number of bits needed to give each of `count` items its own code.
+fn width(count: usize) -> usize {
+    let mut width = 1;
+    while 1 << width < count {
+        width += 1;
+    }
+    width
+}
+
+fn bits(value: usize, width: usize) -> String {
+    format!("{:0width$b}", value, width = width)
+}
+
+fn direction_bits(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::Unchanged => "00",
+        Direction::Right => "01",
+        Direction::Left => "10",
+    }
+}
+
+/// The numbering of states and symbols used by `encode`, needed to map a
+/// band of the universal machine back to the names of the original machine.
+#[derive(Debug, Clone)]
+pub struct UtmEncoding {
+    pub states: Vec<String>,
+    pub symbols: Vec<String>,
+}
+
+impl UtmEncoding {
+    /// The start state comes first and the remaining states in name order,
+    /// the blank comes first and the remaining symbols in sorted order.
+    pub fn new(machine: &Machine) -> Self {
+        let mut states = machine
+            .states
+            .keys()
+            .filter(|name| **name != machine.start_state_name)
+            .cloned()
+            .collect::<Vec<_>>();
+        states.sort();
+        states.insert(0, machine.start_state_name.clone());
+
+        let mut symbols = machine
+            .alphabet
+            .iter()
+            .filter(|s| *s != "_")
+            .cloned()
+            .collect::<Vec<_>>();
+        symbols.sort();
+        symbols.insert(0, "_".to_string());
+
+        Self { states, symbols }
+    }
+
+    fn state_code(&self, machine: &Machine, name: &str) -> String {
+        let index = self.states.iter().position(|s| s == name).unwrap();
+        let flag = if machine.states[name].is_end_state {
+            "1"
+        } else {
+            "0"
+        };
+        format!("{}{}", flag, bits(index, width(self.states.len())))
+    }
+
+    fn symbol_code(&self, symbol: &str) -> Option<String> {
+        let index = self.symbols.iter().position(|s| s == symbol)?;
+        Some(bits(index, width(self.symbols.len())))
+    }
+
+    /// Reads the configuration of the simulated machine from the band of the
+    /// universal machine: the current state and the simulated band without
+    /// trailing blanks.
+    pub fn decode_band(&self, band: &[String]) -> Option<(String, Vec<String>)> {
+        let band = band.concat();
+        let mut sections = band.trim_end_matches('_').split('#').skip(1);
+        let state = sections.next()?;
+        let state = usize::from_str_radix(state.get(1..)?, 2).ok()?;
+        let mut tape = sections
+            .next()?
+            .split(['|', '^'])
+            .skip(1)
+            .map(|cell| {
+                usize::from_str_radix(cell, 2)
+                    .ok()
+                    .and_then(|index| self.symbols.get(index).cloned())
+            })
+            .collect::<Option<Vec<_>>>()?;
+        while tape.last().map(|s| s == "_").unwrap_or(false) {
+            tape.pop();
+        }
+        Some((self.states.get(state)?.clone(), tape))
+    }
+}
+
+/**
+ * Encodes a single band machine and its input for the universal machine
+ *
+ * $;T;T;...;T#Q#^C|C|...|C
+ *
+ * every transition function T is "q,s,n,w,d": the state, the symbol it reads,
+ * the next state, the symbol it writes and the direction ("00" unchanged,
+ * "01" right, "10" left). states are a flag bit (1 for end states) followed by
+ * their index in binary, symbols their index in binary, every field has the
+ * same width for all states or all symbols. Q is the current state and C are
+ * the cells of the band, '^' marks the cell the head is on.
+ */
+pub fn encode(machine: &Machine, input: &[String]) -> Result<(String, UtmEncoding), &'static str> {
+    if machine.size != 1 {
+        return Err("only single band machines can be encoded");
+    }
+    if machine.tape_mode != TapeMode::Clamped {
+        return Err("the universal machine only simulates clamped bands");
+    }
+    let machine = &machine.expand_wildcards();
+    let encoding = UtmEncoding::new(machine);
+
+    let mut out = String::from("$");
+    for name in encoding.states.iter() {
+        for f in machine.states[name].transition_functions.iter() {
+            out.push_str(&format!(
+                ";{},{},{},{},{}",
+                encoding.state_code(machine, name),
+                encoding.symbol_code(&f.bands_requirements[0]).unwrap(),
+                encoding.state_code(machine, &f.next_state_name),
+                encoding.symbol_code(&f.bands_actions[0].0).unwrap(),
+                direction_bits(&f.bands_actions[0].1)
+            ));
+        }
+    }
+    out.push('#');
+    out.push_str(&encoding.state_code(machine, &machine.start_state_name));
+    out.push('#');
+
+    let blank = ["_".to_string()];
+    let input = if input.is_empty() { &blank[..] } else { input };
+    for (index, symbol) in input.iter().enumerate() {
+        out.push(if index == 0 { '^' } else { '|' });
+        out.push_str(
+            &encoding
+                .symbol_code(symbol)
+                .ok_or("input symbol not defined in alphabet")?,
+        );
+    }
+
+    Ok((out, encoding))
+}
+
+/// Splits an encoded machine into the symbols of the universal machine's band.
+pub fn to_band(encoded: &str) -> Vec<String> {
+    encoded.chars().map(String::from).collect()
+}
+
+/// Rebuilds the machine and input from their encoding. Names are lost in the
+/// encoding, states are called `q0`, `q1`, ... by their index and symbols are
+/// named by their index, the blank stays `_`.
+pub fn decode(encoded: &str) -> Result<(Machine, Vec<String>), &'static str> {
+    let mut sections = encoded
+        .strip_prefix('$')
+        .ok_or("encoding has to start with '$'")?
+        .split('#');
+    let (transitions, start, tape) = match (sections.next(), sections.next(), sections.next()) {
+        (Some(transitions), Some(start), Some(tape)) => (transitions, start, tape),
+        _ => return Err("expected transitions, start state and band"),
+    };
+
+    let state = |code: &str| -> Result<(String, bool), &'static str> {
+        let index = code
+            .get(1..)
+            .and_then(|index| usize::from_str_radix(index, 2).ok())
+            .ok_or("invalid state code")?;
+        Ok((format!("q{}", index), code.starts_with('1')))
+    };
+    let symbol = |code: &str| -> Result<String, &'static str> {
+        match usize::from_str_radix(code, 2) {
+            Ok(0) => Ok("_".to_string()),
+            Ok(index) => Ok(index.to_string()),
+            Err(_) => Err("invalid symbol code"),
+        }
+    };
+
+    let mut alphabet = HashSet::new();
+    let mut states: HashMap<String, State> = HashMap::new();
+    let mut add_state = |(name, is_end_state): (String, bool)| {
+        states
+            .entry(name.clone())
+            .or_insert_with(|| State::new(vec![], is_end_state));
+        name
+    };
+    let mut functions = Vec::new();
+    for transition in transitions.split(';').skip(1) {
+        let fields = transition.split(',').collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err("transition needs five fields");
+        }
+        let direction = match fields[4] {
+            "00" => Direction::Unchanged,
+            "01" => Direction::Right,
+            "10" => Direction::Left,
+            _ => return Err("invalid direction"),
+        };
+        let origin = add_state(state(fields[0])?);
+        let next = add_state(state(fields[2])?);
+        let (read, write) = (symbol(fields[1])?, symbol(fields[3])?);
+        alphabet.insert(read.clone());
+        alphabet.insert(write.clone());
+        functions.push(TransitionFunction::new(
+            origin,
+            vec![read],
+            vec![(write, direction)],
+            next,
+        ));
+    }
+    let start = add_state(state(start)?);
+    for f in functions {
+        states
+            .get_mut(&f.origin)
+            .unwrap()
+            .transition_functions
+            .push(f);
+    }
+
+    let input = tape
+        .split(['|', '^'])
+        .skip(1)
+        .map(symbol)
+        .collect::<Result<Vec<_>, _>>()?;
+    alphabet.extend(input.iter().cloned());
+
+    Ok((Machine::new(alphabet, 1, states, start)?, input))
+}
+
+/// Name of the universal machine's end state, reached when the simulated
+/// machine stops in an end state.
+pub const ACCEPT: &str = "accept";
+/// Name of the state the universal machine gets stuck in when the simulated
+/// machine stops in a state that is not an end state.
+pub const REJECT: &str = "reject";
+
+const BITS: [&str; 2] = ["0", "1"];
+const MARKS: [&str; 2] = ["a", "b"];
+const SYMBOLS: [&str; 11] = ["0", "1", "a", "b", ",", ";", ":", "#", "|", "^", "$"];
+
+#[derive(Default)]
+struct Rules(HashMap<String, Vec<TransitionFunction>>);
+
+impl Rules {
+    /// Adds a transition for every symbol in `reads`, `write` of `None`
+    /// keeps the symbol that was read.
+    fn add(
+        &mut self,
+        state: &str,
+        reads: &[&str],
+        write: Option<&str>,
+        direction: Direction,
+        next: &str,
+    ) {
+        for read in reads {
+            self.0
+                .entry(state.to_string())
+                .or_default()
+                .push(TransitionFunction::new(
+                    state.to_string(),
+                    vec![read.to_string()],
+                    vec![(write.unwrap_or(read).to_string(), direction.clone())],
+                    next.to_string(),
+                ));
+        }
+    }
+
+    /// Moves in `direction` until reading `until`, which is handled by later rules.
+    fn skip(&mut self, state: &str, until: &[&str], direction: Direction) {
+        let reads = SYMBOLS
+            .iter()
+            .chain(["_"].iter())
+            .filter(|s| !until.contains(s))
+            .copied()
+            .collect::<Vec<_>>();
+        self.add(state, &reads, None, direction, state);
+    }
+}
+
+/**
+ * Builds the universal machine for bands produced by `encode`
+ *
+ * each step of the simulated machine is done in two parts
+ * - matching: the first transition function still separated by ';' is compared
+ *   bit by bit with the current state and the cell under '^', marking compared
+ *   bits (0 -> a, 1 -> b). on a mismatch its separator becomes ':' and the
+ *   marks are cleared, so the next transition function is tried
+ * - applying: the next state and the written symbol are copied bit by bit over
+ *   the marked current state and cell, then '^' moves and all marks and
+ *   separators are reset
+ *
+ * if no transition function matches, the flag bit of the current state decides
+ * between `ACCEPT` and getting stuck in `REJECT`. like the executor the
+ * simulated head stays on the first cell when moving left from it.
+ */
+pub fn universal_machine() -> Machine {
+    use Direction::*;
+    let mut r = Rules::default();
+
+    // go back to '$' and look for the first transition function not yet rejected
+    r.skip("home", &["$"], Left);
+    r.add("home", &["$"], None, Right, "find");
+    r.skip("find", &[";", "#"], Right);
+    r.add("find", &[";"], None, Right, "match state");
+    r.add("find", &["#"], None, Right, "stop");
+
+    // compare the first two fields with the current state and the cell
+    for (field, next_field, carry) in [
+        ("match state", "match symbol", "carry state "),
+        ("match symbol", "apply", "carry symbol "),
+    ] {
+        r.add(field, &MARKS, None, Right, field);
+        for (bit, mark) in BITS.iter().zip(MARKS) {
+            r.add(
+                field,
+                &[bit],
+                Some(mark),
+                Right,
+                &format!("{}{}", carry, bit),
+            );
+        }
+        if next_field == "apply" {
+            r.add(field, &[","], None, Left, "apply home");
+        } else {
+            r.add(field, &[","], None, Right, next_field);
+        }
+    }
+    for (bit, mark) in BITS.iter().zip(MARKS) {
+        let other = if *bit == "0" { "1" } else { "0" };
+        for (carry, until, compare) in [
+            ("carry state ", "#", "compare state "),
+            ("carry symbol ", "^", "compare symbol "),
+        ] {
+            let carry = format!("{}{}", carry, bit);
+            let compare = format!("{}{}", compare, bit);
+            r.skip(&carry, &[until], Right);
+            r.add(&carry, &[until], None, Right, &compare);
+            r.add(&compare, &MARKS, None, Right, &compare);
+            r.add(&compare, &[bit], Some(mark), Left, "home");
+            r.add(&compare, &[other], None, Left, "reject home");
+        }
+    }
+
+    // mark the rejected transition function and clear all marks
+    r.skip("reject home", &["$"], Left);
+    r.add("reject home", &["$"], None, Right, "reject find");
+    r.skip("reject find", &[";"], Right);
+    r.add("reject find", &[";"], Some(":"), Right, "unmark");
+    r.add("unmark", &["a"], Some("0"), Right, "unmark");
+    r.add("unmark", &["b"], Some("1"), Right, "unmark");
+    r.skip("unmark", &["a", "b", "_"], Right);
+    r.add("unmark", &["_"], None, Left, "home");
+
+    // no transition function matched
+    r.add("stop", &["1"], None, Unchanged, ACCEPT);
+    r.add("stop", &["0"], None, Unchanged, REJECT);
+
+    // copy the next state and the written symbol
+    r.skip("apply home", &["$"], Left);
+    r.add("apply home", &["$"], None, Right, "apply find");
+    r.skip("apply find", &[";"], Right);
+    r.add("apply find", &[";"], None, Right, "apply state");
+    r.add("apply state", &MARKS, None, Right, "apply state");
+    r.add("apply state", &[","], None, Right, "apply symbol");
+    r.add("apply symbol", &MARKS, None, Right, "apply symbol");
+    r.add("apply symbol", &[","], None, Right, "copy state");
+    for (field, next_field, carry) in [
+        ("copy state", "copy symbol", "copy state "),
+        ("copy symbol", "direction", "copy symbol "),
+    ] {
+        r.add(field, &MARKS, None, Right, field);
+        for (bit, mark) in BITS.iter().zip(MARKS) {
+            r.add(
+                field,
+                &[bit],
+                Some(mark),
+                Right,
+                &format!("{}{}", carry, bit),
+            );
+        }
+        r.add(field, &[","], None, Right, next_field);
+    }
+    for bit in BITS {
+        for (carry, until) in [("copy state ", "#"), ("copy symbol ", "^")] {
+            let carry = format!("{}{}", carry, bit);
+            let write = format!("{} write", carry);
+            r.skip(&carry, &[until], Right);
+            r.add(&carry, &[until], None, Right, &write);
+            r.add(&write, &BITS, None, Right, &write);
+            r.add(&write, &MARKS, Some(bit), Left, "apply home");
+        }
+    }
+
+    // move the head marker
+    r.add("direction", &["0"], None, Right, "direction 0");
+    r.add("direction", &["1"], None, Right, "direction 1");
+    r.add("direction 0", &["0"], None, Unchanged, "finish");
+    r.add("direction 0", &["1"], None, Unchanged, "move right");
+    r.add("direction 1", &["0"], None, Unchanged, "move left");
+
+    r.skip("move right", &["^"], Right);
+    r.add("move right", &["^"], Some("|"), Right, "move right cell");
+    r.add("move right cell", &BITS, None, Right, "move right cell");
+    r.add("move right cell", &["|"], Some("^"), Unchanged, "finish");
+    r.add("move right cell", &["_"], Some("^"), Left, "new cell back");
+
+    // append a blank cell as wide as the previous one
+    r.add(
+        "new cell back",
+        &["0", "1", "a", "b", "^"],
+        None,
+        Left,
+        "new cell back",
+    );
+    r.add("new cell back", &["|"], None, Right, "new cell mark");
+    r.add("new cell mark", &MARKS, None, Right, "new cell mark");
+    r.add("new cell mark", &["0"], Some("a"), Right, "new cell end");
+    r.add("new cell mark", &["1"], Some("b"), Right, "new cell end");
+    r.add("new cell mark", &["^"], None, Left, "new cell unmark");
+    r.add(
+        "new cell end",
+        &["0", "1", "a", "b", "^"],
+        None,
+        Right,
+        "new cell end",
+    );
+    r.add("new cell end", &["_"], Some("0"), Left, "new cell back");
+    r.add(
+        "new cell unmark",
+        &["a"],
+        Some("0"),
+        Left,
+        "new cell unmark",
+    );
+    r.add(
+        "new cell unmark",
+        &["b"],
+        Some("1"),
+        Left,
+        "new cell unmark",
+    );
+    r.add("new cell unmark", &["|"], None, Unchanged, "finish");
+
+    r.skip("move left", &["^"], Right);
+    r.add("move left", &["^"], None, Left, "move left check");
+    r.add("move left check", &["#"], None, Unchanged, "finish");
+    r.add("move left check", &BITS, None, Left, "move left cell");
+    r.add("move left cell", &BITS, None, Left, "move left cell");
+    r.add(
+        "move left cell",
+        &["|"],
+        Some("^"),
+        Right,
+        "move left restore",
+    );
+    r.add("move left restore", &BITS, None, Right, "move left restore");
+    r.add("move left restore", &["^"], Some("|"), Unchanged, "finish");
+
+    // reset all marks and separators before the next step
+    r.skip("finish", &["$"], Left);
+    r.add("finish", &["$"], None, Right, "reset");
+    r.add("reset", &["a"], Some("0"), Right, "reset");
+    r.add("reset", &["b"], Some("1"), Right, "reset");
+    r.add("reset", &[":"], Some(";"), Right, "reset");
+    r.skip("reset", &["a", "b", ":", "_"], Right);
+    r.add("reset", &["_"], None, Left, "home");
+
+    let mut states =
+        r.0.into_iter()
+            .map(|(name, functions)| (name, State::new(functions, false)))
+            .collect::<HashMap<_, _>>();
+    states.insert(ACCEPT.to_string(), State::new(vec![], true));
+    states.insert(REJECT.to_string(), State::new(vec![], false));
+
+    Machine::new(
+        SYMBOLS.iter().map(|s| s.to_string()).collect(),
+        1,
+        states,
+        "home".to_string(),
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{MachineExecutor, Simulator};
+    use crate::{compact, tmsim};
+
+    fn run(machine: Machine, input: Vec<String>) -> MachineExecutor {
+        let mut executor = MachineExecutor::new(machine, input);
+        while executor.next_step().unwrap().is_some() {}
+        executor
+    }
+
+    fn trimmed(band: &[String]) -> Vec<String> {
+        let mut band = band.to_vec();
+        while band.last().map(|s| s == "_").unwrap_or(false) {
+            band.pop();
+        }
+        band
+    }
+
+    fn symbols(s: &str) -> Vec<String> {
+        s.chars().map(String::from).collect()
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let machine = compact::from_compact("1RB1LB_1LA1RZ")
+            .unwrap()
+            .with_tape_mode(TapeMode::Clamped);
+        let (encoded, encoding) = encode(&machine, &symbols("1_1")).unwrap();
+        assert_eq!(encoding.states, ["A", "B", "Z"]);
+        assert_eq!(encoding.symbols, ["_", "1"]);
+        assert_eq!(
+            encoded,
+            "$;000,0,001,1,01;000,1,001,1,10;001,0,000,1,10;001,1,110,1,01#000#^1|0|1"
+        );
+
+        // the names are gone, the numbering stays the same
+        let (decoded, input) = decode(&encoded).unwrap();
+        assert_eq!(input, symbols("1_1"));
+        assert_eq!(decoded.start_state_name, "q0");
+        assert!(decoded.states["q2"].is_end_state);
+        assert_eq!(encode(&decoded, &input).unwrap().0, encoded);
+
+        assert_eq!(
+            encode(&machine, &symbols("2")).unwrap_err(),
+            "input symbol not defined in alphabet"
+        );
+        assert_eq!(
+            encode(&machine.with_tape_mode(TapeMode::TwoWayInfinite), &[]).unwrap_err(),
+            "the universal machine only simulates clamped bands"
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        for (encoded, error) in [
+            ("#000#^0", "encoding has to start with '$'"),
+            (
+                "$;000,0,000,0,00#000",
+                "expected transitions, start state and band",
+            ),
+            ("$;000,0,000,0#000#^0", "transition needs five fields"),
+            ("$;000,0,000,0,11#000#^0", "invalid direction"),
+            ("$;0,0,000,0,00#000#^0", "invalid state code"),
+            ("$;000,2,000,0,00#000#^0", "invalid symbol code"),
+        ] {
+            assert_eq!(decode(encoded).err(), Some(error), "{}", encoded);
+        }
+    }
+
+    #[test]
+    fn decode_band() {
+        let machine = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        let encoding = UtmEncoding::new(&machine);
+        assert_eq!(
+            encoding.decode_band(&to_band("$;000,0,001,1,01#001#^1|0|1|0__")),
+            Some(("B".to_string(), symbols("1_1")))
+        );
+        // a missing band, a cell that isn't binary, a symbol or a state that
+        // doesn't exist
+        assert_eq!(encoding.decode_band(&to_band("$;000,0,001,1,01#001")), None);
+        assert_eq!(encoding.decode_band(&to_band("$#001#^1|a")), None);
+        assert_eq!(encoding.decode_band(&to_band("$#001#^1|10")), None);
+        assert_eq!(encoding.decode_band(&to_band("$#011#^1")), None);
+    }
+
+    #[test]
+    fn universal_machine_matches_direct_run() {
+        let increment = tmsim::from_tmsim(
+            "init: right\naccept: done\n\
+             right,0\nright,0,>\nright,1\nright,1,>\nright,_\ncarry,_,<\n\
+             carry,1\ncarry,0,<\ncarry,0\ndone,1,-\ncarry,_\ndone,1,-",
+        )
+        .unwrap();
+        let busy_beaver = compact::from_compact("1RB1LB_1LA1RZ").unwrap();
+        let stuck = compact::from_compact("1RB---_0LA1RB").unwrap();
+        let cases = [
+            (&increment, "1011"),
+            (&increment, "111"),
+            (&busy_beaver, "_"),
+            (&busy_beaver, "11"),
+            (&stuck, "1_1"),
+        ];
+
+        for (machine, input) in cases {
+            // the universal machine simulates a band that is clamped at cell 0
+            let machine = &machine.clone().with_tape_mode(TapeMode::Clamped);
+            let input = symbols(input);
+            let direct = run(machine.clone(), input.clone());
+
+            let (encoded, encoding) = encode(machine, &input).unwrap();
+            let (decoded, decoded_input) = decode(&encoded).unwrap();
+            assert_eq!(encode(&decoded, &decoded_input).unwrap().0, encoded);
+
+            let universal = run(universal_machine(), to_band(&encoded));
+            let (state, band) = encoding.decode_band(&universal.band_symbols()[0]).unwrap();
+            assert_eq!(band, trimmed(&direct.band_symbols()[0]));
+            assert_eq!(machine.states[&state].is_end_state, direct.is_end_state());
+            assert_eq!(universal.is_end_state(), direct.is_end_state());
+        }
+    }
+}
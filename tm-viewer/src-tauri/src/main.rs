@@ -5,16 +5,23 @@
 
 use std::{
     collections::{HashMap, HashSet},
+    fs,
     sync::Mutex,
 };
 
-use app::tm::{MachineExecutor, SimulationStep};
+use compiler::sim::{
+    Breakpoint, Limits, MachineExecutor, RunOutcome, SimulationStep, Simulator, Snapshot,
+    Statistics,
+};
 use compiler::tm::{Direction, Machine, State, TransitionFunction};
+use serde::{Deserialize, Serialize};
 use tauri::Manager;
 
 #[derive(Default)]
 struct AppState {
     machine: Mutex<Option<Machine>>,
+    /// The last run, kept so it can be continued or saved.
+    executor: Mutex<Option<MachineExecutor>>,
 }
 
 #[tauri::command]
@@ -59,20 +66,148 @@ fn get_test_machine(state: tauri::State<'_, AppState>) -> Machine {
     //MachineExecutor::new(machine, "000".chars().collect())
 }
 
+/// Used when the frontend doesn't pass limits, so a machine that doesn't halt
+/// can't hang the app.
+const DEFAULT_LIMITS: Limits = Limits {
+    steps: Some(100_000),
+    cells: Some(100_000),
+    milliseconds: Some(5_000),
+};
+
+/// The executed steps, only up to the limit if one was hit, and why the run
+/// stopped.
+#[derive(Serialize)]
+struct Run {
+    steps: Vec<SimulationStep>,
+    outcome: RunOutcome,
+    /// Since the start of the run, if statistics were requested.
+    statistics: Option<Statistics>,
+}
+
+/// Starts a run of the current machine. With `history` every step is
+/// recorded so the run can be stepped back, which costs memory for each step
+/// up to the step limit. Saving a snapshot doesn't need it.
 #[tauri::command]
-fn accept_input(input: Vec<String>, state: tauri::State<'_, AppState>) -> Vec<SimulationStep> {
-    let mut steps = Vec::new();
+fn accept_input(
+    input: Vec<String>,
+    limits: Option<Limits>,
+    statistics: Option<bool>,
+    detect_cycles: Option<bool>,
+    history: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Run {
     let mut executor = MachineExecutor::new(state.machine.lock().unwrap().clone().unwrap(), input);
-    while let Some(simulation_step) = executor.next_step() {
-        steps.push(simulation_step)
+    if history.unwrap_or(false) {
+        executor = executor.with_history();
+    }
+    if statistics.unwrap_or(false) {
+        executor.collect_statistics();
+    }
+    let run = run(&mut executor, limits, detect_cycles);
+    *state.executor.lock().unwrap() = Some(executor);
+    run
+}
+
+/// Continues the last run, e.g. after it hit a limit or was loaded.
+#[tauri::command]
+fn continue_run(
+    limits: Option<Limits>,
+    detect_cycles: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Run, String> {
+    let mut executor = state.executor.lock().unwrap();
+    let executor = executor.as_mut().ok_or("no run to continue")?;
+    Ok(run(executor, limits, detect_cycles))
+}
+
+/// Continues the last run until one of `breakpoints` is hit, the outcome
+/// holds the index of the breakpoint.
+#[tauri::command]
+fn run_until_breakpoint(
+    breakpoints: Vec<Breakpoint>,
+    limits: Option<Limits>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Run, String> {
+    let mut executor = state.executor.lock().unwrap();
+    let executor = executor.as_mut().ok_or("no run to continue")?;
+    let limits = limits.unwrap_or(DEFAULT_LIMITS);
+    let mut steps = Vec::new();
+    let outcome = executor.run_until_breakpoint(&breakpoints, &limits, |step| steps.push(step));
+    Ok(Run {
+        steps,
+        outcome,
+        statistics: executor.statistics().cloned(),
+    })
+}
+
+/// With `detect_cycles` a run that provably never halts stops with
+/// `RunOutcome::Cycle` or `RunOutcome::TranslatedCycle` instead of running
+/// into a limit.
+fn run(executor: &mut MachineExecutor, limits: Option<Limits>, detect_cycles: Option<bool>) -> Run {
+    let limits = limits.unwrap_or(DEFAULT_LIMITS);
+    let mut steps = Vec::new();
+    let outcome = if detect_cycles.unwrap_or(false) {
+        executor.run_detecting_cycles(&limits, |step| steps.push(step))
+    } else {
+        executor.run_with(&limits, |step| steps.push(step))
+    };
+    Run {
+        steps,
+        outcome,
+        statistics: executor.statistics().cloned(),
+    }
+}
+
+/// A saved run, the machine is included so the file can be shared.
+#[derive(Serialize, Deserialize)]
+struct SavedRun {
+    machine: Machine,
+    snapshot: Snapshot,
+}
+
+#[tauri::command]
+fn save_snapshot(path: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let executor = state.executor.lock().unwrap();
+    let executor = executor.as_ref().ok_or("no run to save")?;
+    let saved = SavedRun {
+        machine: executor.machine().clone(),
+        snapshot: executor.snapshot(),
+    };
+    let json = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+/// Loads a saved run, it becomes the current machine and run. `history` is
+/// the same as for `accept_input`, the history starts at the loaded step.
+#[tauri::command]
+fn load_snapshot(
+    path: String,
+    history: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<SavedRun, String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let saved: SavedRun = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    let mut executor =
+        MachineExecutor::from_snapshot(saved.machine.clone(), saved.snapshot.clone())?;
+    if history.unwrap_or(false) {
+        executor = executor.with_history();
     }
-    steps
+    *state.machine.lock().unwrap() = Some(executor.machine().clone());
+    *state.executor.lock().unwrap() = Some(executor);
+    Ok(saved)
 }
 
 fn main() {
     tauri::Builder::default()
         // This is where you pass in your commands
-        .invoke_handler(tauri::generate_handler![get_test_machine, accept_input])
+        .invoke_handler(tauri::generate_handler![
+            get_test_machine,
+            accept_input,
+            continue_run,
+            run_until_breakpoint,
+            save_snapshot,
+            load_snapshot
+        ])
         .setup(|app_handle| {
             app_handle.manage(AppState::default());
             Ok(())